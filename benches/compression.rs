@@ -0,0 +1,64 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use metrics_datadog_exporter::data::{
+    DataDogApiPost, DataDogMetricType, DataDogMetricValue, DataDogSeries,
+};
+use metrics_datadog_exporter::Compression;
+
+fn series(count: usize) -> Vec<DataDogSeries> {
+    (0..count)
+        .map(|i| DataDogSeries {
+            interval: None,
+            metric: format!("bench.metric.{}", i % 100),
+            points: vec![(1700000000, DataDogMetricValue::Float(i as f64))],
+            tags: vec![format!("host:host-{}", i % 10), "env:bench".to_string()],
+            metric_type: DataDogMetricType::Gauge,
+        })
+        .collect()
+}
+
+fn codecs() -> Vec<(&'static str, Compression)> {
+    #[allow(unused_mut)]
+    let mut codecs = vec![("none", Compression::None)];
+    #[cfg(feature = "gzip")]
+    codecs.extend([
+        ("gzip-1", Compression::Gzip(1)),
+        ("gzip-6", Compression::Gzip(6)),
+        ("gzip-9", Compression::Gzip(9)),
+    ]);
+    #[cfg(feature = "deflate")]
+    codecs.extend([
+        ("deflate-1", Compression::Deflate(1)),
+        ("deflate-6", Compression::Deflate(6)),
+    ]);
+    #[cfg(feature = "zstd")]
+    codecs.extend([
+        ("zstd-1", Compression::Zstd(1)),
+        ("zstd-3", Compression::Zstd(3)),
+        ("zstd-9", Compression::Zstd(9)),
+    ]);
+    codecs
+}
+
+fn compression(c: &mut Criterion) {
+    let series = series(10000);
+    let body = serde_json::to_vec(&DataDogApiPost { series: &series }).unwrap();
+    let mut group = c.benchmark_group("compression");
+    group.throughput(Throughput::Bytes(body.len() as u64));
+    for (name, codec) in codecs() {
+        let compressed = codec.compress(&body).unwrap();
+        println!(
+            "{}: {} -> {} bytes ({:.1}%)",
+            name,
+            body.len(),
+            compressed.len(),
+            compressed.len() as f64 * 100.0 / body.len() as f64
+        );
+        group.bench_with_input(BenchmarkId::from_parameter(name), &body, |b, body| {
+            b.iter(|| codec.compress(body).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, compression);
+criterion_main!(benches);
@@ -0,0 +1,28 @@
+use anyhow::Result;
+use log::LevelFilter;
+use metrics::{counter, gauge, histogram};
+use metrics_datadog_exporter::DataDogBuilder;
+use std::thread::sleep;
+use std::time::Duration;
+
+fn main() -> Result<()> {
+    env_logger::builder()
+        .filter_level(LevelFilter::Debug)
+        .init();
+
+    let scheduler = DataDogBuilder::default()
+        .tags(vec![("tag1".to_string(), "value1".to_string())])
+        .write_to_stdout(true)
+        .build()?
+        .install()?
+        .schedule_blocking(Duration::from_millis(100))?;
+
+    counter!("this.counter", "tag2" => "value2").increment(123);
+    gauge!("this.gauge", "tag3" => "value3").set(234.0);
+    histogram!("this.histogram", "tag4" => "value5").record(345.0);
+    histogram!("this.histogram", "tag4" => "value5").record(456.0);
+
+    sleep(Duration::from_secs(1));
+    scheduler.shutdown();
+    Ok(())
+}
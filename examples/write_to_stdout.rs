@@ -13,10 +13,10 @@ async fn main() -> Result<()> {
         .write_to_stdout(true)
         .build()?
         .install()?;
-    counter!("this.counter", 123, "tag2" => "value2");
-    gauge!("this.gauge", 234.0, "tag3" => "value3");
-    histogram!("this.histogram", 345.0, "tag4" => "value5");
-    histogram!("this.histogram", 456.0, "tag4" => "value5");
+    counter!("this.counter", "tag2" => "value2").increment(123);
+    gauge!("this.gauge", "tag3" => "value3").set(234.0);
+    histogram!("this.histogram", "tag4" => "value5").record(345.0);
+    histogram!("this.histogram", "tag4" => "value5").record(456.0);
     metrics.flush().await?;
     Ok(())
 }
@@ -0,0 +1,40 @@
+use std::fmt;
+use std::sync::Arc;
+
+use secrecy::{ExposeSecret, SecretString};
+
+/// DataDog API key, redacted when printed
+#[derive(Clone)]
+pub struct ApiKey(Arc<SecretString>);
+
+impl ApiKey {
+    /// Creates a new [`ApiKey`]
+    pub fn new(key: impl Into<String>) -> Self {
+        ApiKey(Arc::new(SecretString::from(key.into())))
+    }
+
+    pub(crate) fn expose(&self) -> &str {
+        self.0.expose_secret()
+    }
+}
+
+impl From<String> for ApiKey {
+    fn from(key: String) -> Self {
+        ApiKey::new(key)
+    }
+}
+
+impl From<&str> for ApiKey {
+    fn from(key: &str) -> Self {
+        ApiKey::new(key)
+    }
+}
+
+impl fmt::Debug for ApiKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ApiKey([REDACTED])")
+    }
+}
+
+/// Returns the API key to use for the next flush
+pub(crate) type ApiKeyProvider = Arc<dyn Fn() -> Option<ApiKey> + Send + Sync>;
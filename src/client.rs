@@ -0,0 +1,135 @@
+//! HTTP client configuration
+//!
+use std::time::Duration;
+
+use reqwest::{blocking, Client, NoProxy, Proxy};
+#[cfg(any(feature = "rustls", feature = "native-tls"))]
+use reqwest::{Certificate, Identity};
+
+use crate::Result;
+
+/// Client certificate and private key used for mutual TLS
+#[cfg(any(feature = "rustls", feature = "native-tls"))]
+#[derive(Clone)]
+pub(crate) struct ClientIdentity {
+    pub(crate) certificate_pem: Vec<u8>,
+    pub(crate) key_pem: Vec<u8>,
+}
+
+#[cfg(any(feature = "rustls", feature = "native-tls"))]
+impl ClientIdentity {
+    #[cfg(feature = "rustls")]
+    fn identity(&self) -> Result<Identity> {
+        let pem = [
+            self.certificate_pem.as_slice(),
+            b"\n",
+            self.key_pem.as_slice(),
+        ]
+        .concat();
+        Ok(Identity::from_pem(&pem)?)
+    }
+
+    #[cfg(not(feature = "rustls"))]
+    fn identity(&self) -> Result<Identity> {
+        Ok(Identity::from_pkcs8_pem(
+            &self.certificate_pem,
+            &self.key_pem,
+        )?)
+    }
+}
+
+/// Settings applied to both the async and blocking API clients
+#[derive(Clone, Default)]
+pub(crate) struct ClientConfig {
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) connect_timeout: Option<Duration>,
+    pub(crate) proxy: Option<String>,
+    pub(crate) no_proxy: Option<String>,
+    #[cfg(any(feature = "rustls", feature = "native-tls"))]
+    pub(crate) root_certificates: Vec<Vec<u8>>,
+    #[cfg(any(feature = "rustls", feature = "native-tls"))]
+    pub(crate) identity: Option<ClientIdentity>,
+}
+
+impl ClientConfig {
+    /// Whether any option was set
+    pub(crate) fn is_configured(&self) -> bool {
+        #[allow(unused_mut)]
+        let mut configured = self.timeout.is_some()
+            || self.connect_timeout.is_some()
+            || self.proxy.is_some()
+            || self.no_proxy.is_some();
+        #[cfg(any(feature = "rustls", feature = "native-tls"))]
+        {
+            configured |= !self.root_certificates.is_empty() || self.identity.is_some();
+        }
+        configured
+    }
+}
+
+// `reqwest::ClientBuilder` and `reqwest::blocking::ClientBuilder` share method names but no trait
+macro_rules! configure {
+    ($config:expr, $builder:expr) => {{
+        let config = $config;
+        let mut builder = $builder;
+        #[cfg(feature = "rustls")]
+        {
+            builder = builder.use_rustls_tls();
+        }
+        if let Some(timeout) = config.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(connect_timeout) = config.connect_timeout {
+            builder = builder.connect_timeout(connect_timeout);
+        }
+        if let Some(proxy) = config.proxy()? {
+            builder = builder.proxy(proxy);
+        }
+        #[cfg(any(feature = "rustls", feature = "native-tls"))]
+        {
+            for certificate in config.certificates()? {
+                builder = builder.add_root_certificate(certificate);
+            }
+            if let Some(identity) = &config.identity {
+                builder = builder.identity(identity.identity()?);
+            }
+        }
+        builder.build()?
+    }};
+}
+
+impl ClientConfig {
+    fn proxy(&self) -> Result<Option<Proxy>> {
+        self.proxy
+            .as_ref()
+            .map(|url| {
+                let no_proxy = self
+                    .no_proxy
+                    .as_ref()
+                    .map_or_else(NoProxy::from_env, |n| NoProxy::from_string(n));
+                Ok(Proxy::all(url)?.no_proxy(no_proxy))
+            })
+            .transpose()
+    }
+
+    #[cfg(any(feature = "rustls", feature = "native-tls"))]
+    fn certificates(&self) -> Result<Vec<Certificate>> {
+        let mut certificates = vec![];
+        for pem in &self.root_certificates {
+            certificates.extend(Certificate::from_pem_bundle(pem)?);
+        }
+        Ok(certificates)
+    }
+
+    /// Build the async API client
+    pub(crate) fn client(&self) -> Result<Client> {
+        Ok(configure!(self, Client::builder()))
+    }
+
+    /// Build the blocking API client
+    ///
+    /// Must not be called from within an async runtime
+    pub(crate) fn blocking_client(&self) -> Result<blocking::Client> {
+        Ok(configure!(self, blocking::Client::builder()))
+    }
+}
@@ -0,0 +1,68 @@
+//! Source of metric timestamps
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, TimeZone, Utc};
+use parking_lot::Mutex;
+
+/// Source of the timestamps sent with metrics and service checks, see
+/// [`DataDogBuilder::clock`](crate::DataDogBuilder::clock)
+pub trait Clock: Send + Sync {
+    /// Current time
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// System time, the default [`Clock`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// Clock only moving when set or advanced, e.g. for exact tests against payloads
+///
+/// Clones share the same time.
+#[derive(Debug, Clone)]
+pub struct ManualClock(Arc<Mutex<DateTime<Utc>>>);
+
+impl ManualClock {
+    /// Create a clock at `now`
+    pub fn new(now: DateTime<Utc>) -> Self {
+        ManualClock(Arc::new(Mutex::new(now)))
+    }
+
+    /// Create a clock at `secs` seconds since the Unix epoch
+    pub fn from_timestamp(secs: i64) -> Self {
+        ManualClock::new(Utc.timestamp_opt(secs, 0).unwrap())
+    }
+
+    /// Set the current time
+    pub fn set(&self, now: DateTime<Utc>) {
+        *self.0.lock() = now;
+    }
+
+    /// Move the current time forward
+    pub fn advance(&self, duration: Duration) {
+        let mut now = self.0.lock();
+        *now += chrono::Duration::from_std(duration).expect("duration out of range");
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.0.lock()
+    }
+}
+
+/// Timestamp of a flush in seconds, rounded down to a multiple of `align` if set
+pub(crate) fn timestamp(clock: &dyn Clock, align: Option<Duration>) -> i64 {
+    let now = clock.now().timestamp();
+    match align.map(|a| a.as_secs() as i64) {
+        Some(secs) if secs > 0 => now - now.rem_euclid(secs),
+        _ => now,
+    }
+}
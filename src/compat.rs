@@ -0,0 +1,79 @@
+//! [`metrics_021::Recorder`] implementation for code still using metrics 0.21
+//!
+use std::sync::Arc;
+
+use metrics::{CounterFn, GaugeFn, HistogramFn, Key, Label};
+use metrics_021::{KeyName, SharedString, Unit};
+
+use crate::recorder::DataDogRecorder;
+use crate::tags::normalize_key;
+
+/// Forwards metrics 0.21 handle calls to the current storage
+struct Compat<T>(Arc<T>);
+
+impl<T: CounterFn> metrics_021::CounterFn for Compat<T> {
+    fn increment(&self, value: u64) {
+        self.0.increment(value)
+    }
+
+    fn absolute(&self, value: u64) {
+        self.0.absolute(value)
+    }
+}
+
+impl<T: GaugeFn> metrics_021::GaugeFn for Compat<T> {
+    fn increment(&self, value: f64) {
+        self.0.increment(value)
+    }
+
+    fn decrement(&self, value: f64) {
+        self.0.decrement(value)
+    }
+
+    fn set(&self, value: f64) {
+        self.0.set(value)
+    }
+}
+
+impl<T: HistogramFn> metrics_021::HistogramFn for Compat<T> {
+    fn record(&self, value: f64) {
+        self.0.record(value)
+    }
+}
+
+fn convert_key(key: &metrics_021::Key) -> Key {
+    let labels = key
+        .labels()
+        .map(|label| Label::new(label.key().to_string(), label.value().to_string()))
+        .collect::<Vec<_>>();
+    let key = Key::from_parts(key.name().to_string(), labels);
+    normalize_key(&key).unwrap_or(key)
+}
+
+// metrics 0.21 has no metadata, so target filters don't apply
+impl metrics_021::Recorder for DataDogRecorder {
+    fn describe_counter(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {}
+
+    fn describe_gauge(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {}
+
+    fn describe_histogram(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {}
+
+    fn register_counter(&self, key: &metrics_021::Key) -> metrics_021::Counter {
+        self.registry.get_or_create_counter(&convert_key(key), |c| {
+            metrics_021::Counter::from_arc(Arc::new(Compat(c.clone())))
+        })
+    }
+
+    fn register_gauge(&self, key: &metrics_021::Key) -> metrics_021::Gauge {
+        self.registry.get_or_create_gauge(&convert_key(key), |g| {
+            metrics_021::Gauge::from_arc(Arc::new(Compat(g.clone())))
+        })
+    }
+
+    fn register_histogram(&self, key: &metrics_021::Key) -> metrics_021::Histogram {
+        self.registry
+            .get_or_create_histogram(&convert_key(key), |h| {
+                metrics_021::Histogram::from_arc(Arc::new(Compat(h.clone())))
+            })
+    }
+}
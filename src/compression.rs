@@ -0,0 +1,178 @@
+//! API payload compression
+//!
+#[cfg(any(feature = "gzip", feature = "deflate", feature = "zstd"))]
+use std::io::Write;
+
+use crate::Result;
+
+/// Compression applied to DataDog API payloads
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    /// No compression
+    None,
+    /// gzip with level 0-9
+    #[cfg(feature = "gzip")]
+    Gzip(u32),
+    /// zlib deflate with level 0-9
+    #[cfg(feature = "deflate")]
+    Deflate(u32),
+    /// zstd with level 1-22
+    #[cfg(feature = "zstd")]
+    Zstd(i32),
+}
+
+#[allow(clippy::derivable_impls)]
+impl Default for Compression {
+    fn default() -> Self {
+        #[cfg(feature = "gzip")]
+        {
+            Compression::Gzip(6)
+        }
+        #[cfg(not(feature = "gzip"))]
+        {
+            Compression::None
+        }
+    }
+}
+
+impl Compression {
+    /// Value of the `Content-Encoding` header
+    pub fn content_encoding(&self) -> Option<&'static str> {
+        match self {
+            Compression::None => None,
+            #[cfg(feature = "gzip")]
+            Compression::Gzip(_) => Some("gzip"),
+            #[cfg(feature = "deflate")]
+            Compression::Deflate(_) => Some("deflate"),
+            // https://docs.datadoghq.com/api/latest/metrics/#submit-metrics
+            #[cfg(feature = "zstd")]
+            Compression::Zstd(_) => Some("zstd1"),
+        }
+    }
+
+    /// Describes the problem when the level is out of range for the codec
+    pub(crate) fn invalid_level(&self) -> Option<String> {
+        match *self {
+            Compression::None => None,
+            #[cfg(feature = "gzip")]
+            Compression::Gzip(level) if level > 9 => {
+                Some(format!("gzip level {} is not in 0-9", level))
+            }
+            #[cfg(feature = "deflate")]
+            Compression::Deflate(level) if level > 9 => {
+                Some(format!("deflate level {} is not in 0-9", level))
+            }
+            #[cfg(feature = "zstd")]
+            Compression::Zstd(level) if !(1..=22).contains(&level) => {
+                Some(format!("zstd level {} is not in 1-22", level))
+            }
+            #[allow(unreachable_patterns)]
+            _ => None,
+        }
+    }
+
+    /// Compress a payload
+    pub fn compress(&self, body: &[u8]) -> Result<Vec<u8>> {
+        let mut encoder = Encoder::new(*self)?;
+        encoder.write_all(body)?;
+        encoder.finish()
+    }
+}
+
+/// Streaming encoder for a single payload
+pub(crate) enum Encoder {
+    None(Vec<u8>),
+    #[cfg(feature = "gzip")]
+    Gzip(flate2::write::GzEncoder<Vec<u8>>),
+    #[cfg(feature = "deflate")]
+    Deflate(flate2::write::ZlibEncoder<Vec<u8>>),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::Encoder<'static, Vec<u8>>),
+}
+
+impl Encoder {
+    pub(crate) fn new(compression: Compression) -> Result<Self> {
+        Ok(match compression {
+            Compression::None => Encoder::None(Vec::new()),
+            #[cfg(feature = "gzip")]
+            Compression::Gzip(level) => Encoder::Gzip(flate2::write::GzEncoder::new(
+                Vec::new(),
+                flate2::Compression::new(level),
+            )),
+            #[cfg(feature = "deflate")]
+            Compression::Deflate(level) => Encoder::Deflate(flate2::write::ZlibEncoder::new(
+                Vec::new(),
+                flate2::Compression::new(level),
+            )),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd(level) => Encoder::Zstd(zstd::Encoder::new(Vec::new(), level)?),
+        })
+    }
+
+    /// Upper bound of the encoded size of `len` bytes, including framing
+    ///
+    /// Covers both zlib's `deflateBound` and zstd's `ZSTD_compressBound`
+    pub(crate) fn bound(&self, len: usize) -> usize {
+        match self {
+            Encoder::None(_) => len,
+            #[allow(unreachable_patterns)]
+            _ => len + (len >> 7) + 64,
+        }
+    }
+
+    pub(crate) fn is_compressed(&self) -> bool {
+        !matches!(self, Encoder::None(_))
+    }
+
+    /// Bytes output by the encoder so far
+    pub(crate) fn encoded_len(&self) -> usize {
+        match self {
+            Encoder::None(v) => v.len(),
+            #[cfg(feature = "gzip")]
+            Encoder::Gzip(e) => e.get_ref().len(),
+            #[cfg(feature = "deflate")]
+            Encoder::Deflate(e) => e.get_ref().len(),
+            #[cfg(feature = "zstd")]
+            Encoder::Zstd(e) => e.get_ref().len(),
+        }
+    }
+
+    pub(crate) fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+        match self {
+            Encoder::None(v) => v.extend_from_slice(buf),
+            #[cfg(feature = "gzip")]
+            Encoder::Gzip(e) => e.write_all(buf)?,
+            #[cfg(feature = "deflate")]
+            Encoder::Deflate(e) => e.write_all(buf)?,
+            #[cfg(feature = "zstd")]
+            Encoder::Zstd(e) => e.write_all(buf)?,
+        };
+        Ok(())
+    }
+
+    /// Flush buffered input so [`Encoder::encoded_len`] is exact
+    pub(crate) fn flush(&mut self) -> Result<()> {
+        match self {
+            Encoder::None(_) => (),
+            #[cfg(feature = "gzip")]
+            Encoder::Gzip(e) => e.flush()?,
+            #[cfg(feature = "deflate")]
+            Encoder::Deflate(e) => e.flush()?,
+            #[cfg(feature = "zstd")]
+            Encoder::Zstd(e) => e.flush()?,
+        };
+        Ok(())
+    }
+
+    pub(crate) fn finish(self) -> Result<Vec<u8>> {
+        Ok(match self {
+            Encoder::None(v) => v,
+            #[cfg(feature = "gzip")]
+            Encoder::Gzip(e) => e.finish()?,
+            #[cfg(feature = "deflate")]
+            Encoder::Deflate(e) => e.finish()?,
+            #[cfg(feature = "zstd")]
+            Encoder::Zstd(e) => e.finish()?,
+        })
+    }
+}
@@ -1,19 +1,23 @@
 //! Data model
 //!
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
 
-use chrono::Utc;
 use itertools::Itertools;
 use metrics::atomics::AtomicU64;
 use metrics::{Key, Label};
-use metrics_util::AtomicBucket;
+use metrics_util::storage::AtomicBucket;
 
 use serde::{Deserialize, Serialize};
 use serde_with::skip_serializing_none;
 
+use crate::tags::FlushTags;
+
 /// Metric type
-#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq, PartialOrd, Ord)]
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq, PartialOrd, Ord, Hash)]
 pub enum DataDogMetricType {
     /// Counter
     #[serde(rename = "count")]
@@ -36,6 +40,24 @@ pub enum DataDogMetricValue {
     Unsigned(u64),
 }
 
+impl DataDogMetricValue {
+    fn as_f64(&self) -> f64 {
+        match self {
+            DataDogMetricValue::Float(v) => *v,
+            DataDogMetricValue::Unsigned(v) => *v as f64,
+        }
+    }
+}
+
+impl Display for DataDogMetricValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            DataDogMetricValue::Float(v) => v.fmt(f),
+            DataDogMetricValue::Unsigned(v) => v.fmt(f),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialOrd, PartialEq)]
 /// DataDog formatted metric
 pub struct DataDogMetric {
@@ -43,10 +65,8 @@ pub struct DataDogMetric {
     pub metric: String,
     /// Metric type
     pub metric_type: DataDogMetricType,
-    /// Metric values
-    pub points: Vec<DataDogMetricValue>,
-    /// Timestamp
-    pub timestamp: i64,
+    /// Metric values with their timestamp
+    pub points: Vec<(i64, DataDogMetricValue)>,
     /// Tags
     pub tags: Vec<String>,
 }
@@ -55,7 +75,8 @@ impl DataDogMetric {
     pub(crate) fn from_counter(
         key: Key,
         values: Vec<Arc<AtomicU64>>,
-        global_tags: &[Label],
+        timestamp: i64,
+        tags: &FlushTags,
     ) -> Self {
         let values = values
             .into_iter()
@@ -64,10 +85,15 @@ impl DataDogMetric {
                 DataDogMetricValue::Unsigned(u)
             })
             .collect_vec();
-        DataDogMetric::from_metric_value(DataDogMetricType::Count, key, values, global_tags)
+        DataDogMetric::from_metric_value(DataDogMetricType::Count, key, values, timestamp, tags)
     }
 
-    pub(crate) fn from_gauge(key: Key, values: Vec<Arc<AtomicU64>>, global_tags: &[Label]) -> Self {
+    pub(crate) fn from_gauge(
+        key: Key,
+        values: Vec<Arc<AtomicU64>>,
+        timestamp: i64,
+        tags: &FlushTags,
+    ) -> Self {
         let values = values
             .into_iter()
             .map(|value| {
@@ -75,47 +101,64 @@ impl DataDogMetric {
                 DataDogMetricValue::Float(u)
             })
             .collect_vec();
-        DataDogMetric::from_metric_value(DataDogMetricType::Gauge, key, values, global_tags)
+        DataDogMetric::from_metric_value(DataDogMetricType::Gauge, key, values, timestamp, tags)
     }
 
     pub(crate) fn from_histogram(
         key: Key,
         values: Vec<Arc<AtomicBucket<f64>>>,
-        global_tags: &[Label],
+        timestamp: i64,
+        tags: &FlushTags,
     ) -> Self {
         let values = values
             .into_iter()
             .flat_map(|value| value.data().into_iter().map(DataDogMetricValue::Float))
             .collect_vec();
-        DataDogMetric::from_metric_value(DataDogMetricType::Histogram, key, values, global_tags)
+        DataDogMetric::from_metric_value(DataDogMetricType::Histogram, key, values, timestamp, tags)
     }
 
     fn from_metric_value(
         metric_type: DataDogMetricType,
         key: Key,
         values: Vec<DataDogMetricValue>,
-        global_tags: &[Label],
+        timestamp: i64,
+        tags: &FlushTags,
     ) -> Self {
         DataDogMetric {
             metric: key.name().to_string(),
             metric_type,
-            points: values,
-            timestamp: Utc::now().timestamp(),
-            tags: global_tags
-                .iter()
-                .chain(key.labels())
-                .map(|l| format!("{}:{}", l.key(), l.value()))
-                .collect(),
+            points: values.into_iter().map(|v| (timestamp, v)).collect(),
+            tags: tags.metric_tags(&key),
         }
     }
 
+    /// Format as DogStatsD datagrams, one per point
+    #[cfg(any(feature = "stdout", feature = "dogstatsd"))]
+    pub(crate) fn to_dogstatsd(&self) -> Vec<String> {
+        let metric_type = match self.metric_type {
+            DataDogMetricType::Count => "c",
+            DataDogMetricType::Gauge => "g",
+            DataDogMetricType::Histogram => "h",
+        };
+        let tags = if self.tags.is_empty() {
+            String::new()
+        } else {
+            format!("|#{}", self.tags.join(","))
+        };
+        self.points
+            .iter()
+            .map(|(_, v)| format!("{}:{}|{}{}", self.metric, v, metric_type, tags))
+            .collect()
+    }
+
+    #[cfg(feature = "stdout")]
     pub(crate) fn to_metric_lines(&self) -> Vec<DataDogMetricLine> {
         self.points
             .iter()
-            .map(|v| DataDogMetricLine {
+            .map(|(timestamp, v)| DataDogMetricLine {
                 name: self.metric.to_string(),
                 value: v.clone(),
-                timestamp: self.timestamp,
+                timestamp: *timestamp,
                 tags: self.tags.clone(),
             })
             .collect()
@@ -146,6 +189,8 @@ pub struct DataDogApiPost<'a> {
     pub series: &'a [DataDogSeries],
 }
 
+const MAX_POINTS_PER_SERIES: usize = 3;
+
 /// DataDog Metric Series
 #[skip_serializing_none]
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -166,15 +211,373 @@ pub struct DataDogSeries {
 impl DataDogSeries {
     /// Create metric series from metric
     pub fn new(m: DataDogMetric) -> Vec<DataDogSeries> {
-        m.points
-            .chunks(3)
-            .map(|points| DataDogSeries {
-                interval: None,
-                metric: m.metric.to_owned(),
-                points: points.iter().map(|v| (m.timestamp, v.to_owned())).collect(),
-                tags: m.tags.to_owned(),
-                metric_type: m.metric_type.to_owned(),
+        DataDogSeries::group(vec![m], None)
+    }
+
+    /// Create metric series from the metrics of one or more intervals
+    ///
+    /// Points of metrics with the same name, type and tags are merged in timestamp order. Count
+    /// points sharing a timestamp are summed and the last gauge point wins. Counts spanning
+    /// several intervals get the shortest interval between points, others `flush_interval` in
+    /// seconds.
+    pub fn group(
+        metrics: impl IntoIterator<Item = DataDogMetric>,
+        flush_interval: Option<i64>,
+    ) -> Vec<DataDogSeries> {
+        let mut grouped: Vec<DataDogMetric> = vec![];
+        let mut index: HashMap<_, usize> = HashMap::new();
+        for m in metrics {
+            let key = (m.metric.clone(), m.metric_type.clone(), m.tags.clone());
+            match index.entry(key) {
+                Entry::Occupied(entry) => grouped[*entry.get()].points.extend(m.points),
+                Entry::Vacant(entry) => {
+                    entry.insert(grouped.len());
+                    grouped.push(m);
+                }
+            }
+        }
+        grouped
+            .into_iter()
+            .flat_map(|mut m| {
+                m.points.sort_by_key(|(timestamp, _)| *timestamp);
+                let points = combine(&m.metric_type, m.points);
+                let interval = match m.metric_type {
+                    DataDogMetricType::Count => interval(&points).or(flush_interval),
+                    _ => None,
+                };
+                points
+                    .chunks(MAX_POINTS_PER_SERIES)
+                    .map(|points| DataDogSeries {
+                        interval,
+                        metric: m.metric.to_owned(),
+                        points: points.to_vec(),
+                        tags: m.tags.to_owned(),
+                        metric_type: m.metric_type.to_owned(),
+                    })
+                    .collect_vec()
             })
             .collect_vec()
     }
 }
+
+/// Number of series [`DataDogSeries::group`] builds from `metrics`, and their points
+pub(crate) fn series_len(metrics: &[DataDogMetric]) -> (usize, usize) {
+    let mut timestamps = HashMap::new();
+    for m in metrics {
+        timestamps
+            .entry((&m.metric, &m.metric_type, &m.tags))
+            .or_insert_with(Vec::new)
+            .extend(m.points.iter().map(|(timestamp, _)| *timestamp));
+    }
+    let points = timestamps
+        .into_iter()
+        .map(|((_, metric_type, _), timestamps)| match metric_type {
+            DataDogMetricType::Histogram => timestamps.len(),
+            _ => timestamps.into_iter().unique().count(),
+        })
+        .collect_vec();
+    let series = points
+        .iter()
+        .map(|n| n.div_ceil(MAX_POINTS_PER_SERIES))
+        .sum();
+    (series, points.iter().sum())
+}
+
+/// Sum count points and keep the last gauge point sharing a timestamp of sorted points
+fn combine(
+    metric_type: &DataDogMetricType,
+    points: Vec<(i64, DataDogMetricValue)>,
+) -> Vec<(i64, DataDogMetricValue)> {
+    if *metric_type == DataDogMetricType::Histogram {
+        return points;
+    }
+    points
+        .into_iter()
+        .coalesce(|(t1, v1), (t2, v2)| {
+            if t1 != t2 {
+                return Err(((t1, v1), (t2, v2)));
+            }
+            let value = match (metric_type, v1, v2) {
+                (
+                    DataDogMetricType::Count,
+                    DataDogMetricValue::Unsigned(a),
+                    DataDogMetricValue::Unsigned(b),
+                ) => DataDogMetricValue::Unsigned(a.saturating_add(b)),
+                (DataDogMetricType::Count, a, b) => {
+                    DataDogMetricValue::Float(a.as_f64() + b.as_f64())
+                }
+                (_, _, b) => b,
+            };
+            Ok((t1, value))
+        })
+        .collect()
+}
+
+/// Shortest gap between timestamps of sorted, combined points
+fn interval(points: &[(i64, DataDogMetricValue)]) -> Option<i64> {
+    points
+        .iter()
+        .map(|(timestamp, _)| *timestamp)
+        .tuple_windows()
+        .map(|(a, b)| b - a)
+        .min()
+}
+
+/// Service check status
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum DataDogServiceCheckStatus {
+    /// OK
+    Ok = 0,
+    /// Warning
+    Warning = 1,
+    /// Critical
+    Critical = 2,
+    /// Unknown
+    Unknown = 3,
+}
+
+impl Serialize for DataDogServiceCheckStatus {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u8(*self as u8)
+    }
+}
+
+/// DataDog Service Check
+#[skip_serializing_none]
+#[derive(Debug, Serialize, Clone)]
+pub struct DataDogServiceCheck {
+    /// Check name
+    pub check: String,
+    /// Host the check applies to
+    pub host_name: Option<String>,
+    /// Check status
+    pub status: DataDogServiceCheckStatus,
+    /// Timestamp
+    pub timestamp: i64,
+    /// Check message
+    pub message: Option<String>,
+    /// Tags
+    pub tags: Vec<String>,
+}
+
+impl DataDogServiceCheck {
+    pub(crate) fn new(
+        check: String,
+        status: DataDogServiceCheckStatus,
+        tags: &[Label],
+        message: Option<String>,
+        host_name: Option<String>,
+        timestamp: i64,
+    ) -> Self {
+        DataDogServiceCheck {
+            check,
+            host_name,
+            status,
+            timestamp,
+            message,
+            tags: tags
+                .iter()
+                .map(|l| format!("{}:{}", l.key(), l.value()))
+                .collect(),
+        }
+    }
+
+    /// Merges global and dynamic tags of the flush
+    pub(crate) fn with_flush_tags(self, flush_tags: &FlushTags) -> Self {
+        DataDogServiceCheck {
+            tags: flush_tags.merge_tags(&self.tags),
+            ..self
+        }
+    }
+
+    /// Format as a DogStatsD `_sc` datagram
+    pub fn to_dogstatsd(&self) -> String {
+        let mut line = format!(
+            "_sc|{}|{}|d:{}",
+            self.check, self.status as u8, self.timestamp
+        );
+        if let Some(host_name) = &self.host_name {
+            line.push_str(&format!("|h:{}", host_name));
+        }
+        if !self.tags.is_empty() {
+            line.push_str(&format!("|#{}", self.tags.join(",")));
+        }
+        if let Some(message) = &self.message {
+            // message must be the last field and can't contain newlines
+            line.push_str(&format!("|m:{}", message.replace('\n', "\\n")));
+        }
+        line
+    }
+}
+
+/// Event alert type
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DataDogEventAlertType {
+    /// Error
+    Error,
+    /// Warning
+    Warning,
+    /// Info
+    Info,
+    /// Success
+    Success,
+}
+
+impl DataDogEventAlertType {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            DataDogEventAlertType::Error => "error",
+            DataDogEventAlertType::Warning => "warning",
+            DataDogEventAlertType::Info => "info",
+            DataDogEventAlertType::Success => "success",
+        }
+    }
+}
+
+/// Event priority
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DataDogEventPriority {
+    /// Normal
+    Normal,
+    /// Low
+    Low,
+}
+
+impl DataDogEventPriority {
+    fn as_str(&self) -> &'static str {
+        match self {
+            DataDogEventPriority::Normal => "normal",
+            DataDogEventPriority::Low => "low",
+        }
+    }
+}
+
+/// DataDog Event
+#[skip_serializing_none]
+#[derive(Debug, Serialize, Clone)]
+pub struct DataDogEvent {
+    /// Event title
+    pub title: String,
+    /// Event body
+    pub text: String,
+    /// Alert type
+    pub alert_type: Option<DataDogEventAlertType>,
+    /// Priority
+    pub priority: Option<DataDogEventPriority>,
+    /// Key used to group events together
+    pub aggregation_key: Option<String>,
+    /// Timestamp, set from the exporter's clock when queued if [`None`]
+    pub date_happened: Option<i64>,
+    /// Host the event applies to
+    pub host: Option<String>,
+    /// Tags
+    pub tags: Vec<String>,
+}
+
+impl DataDogEvent {
+    /// Create an event with a title and text
+    pub fn new(title: String, text: String) -> Self {
+        DataDogEvent {
+            title,
+            text,
+            alert_type: None,
+            priority: None,
+            aggregation_key: None,
+            date_happened: None,
+            host: None,
+            tags: vec![],
+        }
+    }
+
+    /// Set alert type
+    #[must_use]
+    pub fn alert_type(self, alert_type: DataDogEventAlertType) -> Self {
+        DataDogEvent {
+            alert_type: Some(alert_type),
+            ..self
+        }
+    }
+
+    /// Set priority
+    #[must_use]
+    pub fn priority(self, priority: DataDogEventPriority) -> Self {
+        DataDogEvent {
+            priority: Some(priority),
+            ..self
+        }
+    }
+
+    /// Set aggregation key
+    #[must_use]
+    pub fn aggregation_key(self, aggregation_key: String) -> Self {
+        DataDogEvent {
+            aggregation_key: Some(aggregation_key),
+            ..self
+        }
+    }
+
+    /// Set when the event happened, in seconds since the Unix epoch
+    #[must_use]
+    pub fn date_happened(self, date_happened: i64) -> Self {
+        DataDogEvent {
+            date_happened: Some(date_happened),
+            ..self
+        }
+    }
+
+    /// Set tags to send with the event
+    #[must_use]
+    pub fn tags(self, tags: Vec<(String, String)>) -> Self {
+        DataDogEvent {
+            tags: tags
+                .into_iter()
+                .map(|(k, v)| format!("{}:{}", k, v))
+                .collect(),
+            ..self
+        }
+    }
+
+    /// Format as a DogStatsD `_e` datagram
+    pub fn to_dogstatsd(&self) -> String {
+        let title = self.title.replace('\n', "\\n");
+        let text = self.text.replace('\n', "\\n");
+        let mut line = format!("_e{{{},{}}}:{}|{}", title.len(), text.len(), title, text);
+        if let Some(date_happened) = self.date_happened {
+            line.push_str(&format!("|d:{}", date_happened));
+        }
+        if let Some(host) = &self.host {
+            line.push_str(&format!("|h:{}", host));
+        }
+        if let Some(priority) = &self.priority {
+            line.push_str(&format!("|p:{}", priority.as_str()));
+        }
+        if let Some(alert_type) = &self.alert_type {
+            line.push_str(&format!("|t:{}", alert_type.as_str()));
+        }
+        if let Some(aggregation_key) = &self.aggregation_key {
+            line.push_str(&format!("|k:{}", aggregation_key));
+        }
+        if !self.tags.is_empty() {
+            line.push_str(&format!("|#{}", self.tags.join(",")));
+        }
+        line
+    }
+
+    pub(crate) fn with_defaults(self, host: Option<String>, date_happened: i64) -> Self {
+        DataDogEvent {
+            host: self.host.or(host),
+            date_happened: self.date_happened.or(Some(date_happened)),
+            ..self
+        }
+    }
+
+    /// Merges global and dynamic tags of the flush
+    pub(crate) fn with_flush_tags(self, flush_tags: &FlushTags) -> Self {
+        DataDogEvent {
+            tags: flush_tags.merge_tags(&self.tags),
+            ..self
+        }
+    }
+}
@@ -0,0 +1,163 @@
+//! Tags detected from the cloud environment
+//!
+#[cfg(feature = "api")]
+use std::sync::{Arc, OnceLock};
+#[cfg(feature = "api")]
+use std::time::Duration;
+
+#[cfg(feature = "api")]
+use serde::Deserialize;
+#[cfg(feature = "api")]
+use tracing::warn;
+
+/// Detects standard tags from the environment the process runs in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Detector {
+    /// `kube_namespace`, `pod_name`, `kube_deployment` and `kube_node` from the `POD_NAMESPACE`,
+    /// `POD_NAME`, `KUBE_DEPLOYMENT` and `NODE_NAME` variables set with the downward API
+    Kubernetes,
+    /// `ecs_cluster_name`, `task_arn`, `task_family`, `task_version` and `availability_zone` from
+    /// the task metadata endpoint at `ECS_CONTAINER_METADATA_URI_V4`
+    #[cfg(feature = "api")]
+    Ecs,
+    /// `functionname`, `region` and `memorysize` of an AWS Lambda function
+    Lambda,
+}
+
+impl Detector {
+    /// Detect tags from the process environment
+    ///
+    /// [`Detector::Ecs`] requests the task metadata endpoint on another thread and waits up to
+    /// two seconds for it.
+    pub fn detect(&self) -> Vec<(String, String)> {
+        if self.is_remote() {
+            // The blocking client can't run inside an async runtime
+            let detector = *self;
+            return std::thread::spawn(move || detector.detect_from(env_var))
+                .join()
+                .unwrap_or_default();
+        }
+        self.detect_from(env_var)
+    }
+
+    /// Detect tags reading environment variables with `env`
+    #[cfg(feature = "testing")]
+    pub fn detect_with<F>(&self, env: F) -> Vec<(String, String)>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        self.detect_from(env)
+    }
+
+    /// Whether detection makes network requests
+    pub(crate) fn is_remote(&self) -> bool {
+        #[cfg(feature = "api")]
+        if *self == Detector::Ecs {
+            return true;
+        }
+        false
+    }
+
+    /// Detect tags on a background thread, the cell is empty until detection finishes
+    #[cfg(feature = "api")]
+    pub(crate) fn detect_in_background(self) -> Arc<OnceLock<Vec<(String, String)>>> {
+        let detected = Arc::new(OnceLock::new());
+        let cell = detected.clone();
+        std::thread::spawn(move || {
+            let _ = cell.set(self.detect_from(env_var));
+        });
+        detected
+    }
+
+    fn detect_from<F>(&self, env: F) -> Vec<(String, String)>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        match self {
+            Detector::Kubernetes => {
+                if env("KUBERNETES_SERVICE_HOST").is_none() {
+                    return vec![];
+                }
+                from_env(
+                    &env,
+                    &[
+                        ("kube_namespace", "POD_NAMESPACE"),
+                        ("pod_name", "POD_NAME"),
+                        ("kube_deployment", "KUBE_DEPLOYMENT"),
+                        ("kube_node", "NODE_NAME"),
+                    ],
+                )
+            }
+            #[cfg(feature = "api")]
+            Detector::Ecs => match env("ECS_CONTAINER_METADATA_URI_V4") {
+                Some(uri) => ecs_tags(uri),
+                None => vec![],
+            },
+            Detector::Lambda => from_env(
+                &env,
+                &[
+                    ("functionname", "AWS_LAMBDA_FUNCTION_NAME"),
+                    ("region", "AWS_REGION"),
+                    ("memorysize", "AWS_LAMBDA_FUNCTION_MEMORY_SIZE"),
+                ],
+            ),
+        }
+    }
+}
+
+fn env_var(name: &str) -> Option<String> {
+    std::env::var(name).ok()
+}
+
+fn from_env<F>(env: &F, names: &[(&str, &str)]) -> Vec<(String, String)>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    names
+        .iter()
+        .filter_map(|(tag, name)| env(name).map(|value| (tag.to_string(), value)))
+        .collect()
+}
+
+/// Task metadata, see https://docs.aws.amazon.com/AmazonECS/latest/developerguide/task-metadata-endpoint-v4.html
+#[cfg(feature = "api")]
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct EcsTask {
+    cluster: Option<String>,
+    #[serde(rename = "TaskARN")]
+    task_arn: Option<String>,
+    family: Option<String>,
+    revision: Option<String>,
+    availability_zone: Option<String>,
+}
+
+#[cfg(feature = "api")]
+fn ecs_tags(uri: String) -> Vec<(String, String)> {
+    let task = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(2))
+        .build()
+        .and_then(|client| client.get(format!("{}/task", uri)).send())
+        .and_then(|response| response.error_for_status())
+        .and_then(|response| response.json::<EcsTask>());
+    match task {
+        Ok(task) => [
+            (
+                "ecs_cluster_name",
+                task.cluster
+                    .map(|cluster| cluster.rsplit('/').next().unwrap_or_default().to_string()),
+            ),
+            ("task_arn", task.task_arn),
+            ("task_family", task.family),
+            ("task_version", task.revision),
+            ("availability_zone", task.availability_zone),
+        ]
+        .into_iter()
+        .filter_map(|(tag, value)| value.map(|value| (tag.to_string(), value)))
+        .collect(),
+        Err(e) => {
+            warn!(error = %e, "Failed to read ECS task metadata");
+            vec![]
+        }
+    }
+}
@@ -1,203 +1,264 @@
 //! DataDog HTTP API exporter
 
-use flate2::write::GzEncoder;
-use flate2::Compression;
-use futures::future::try_join_all;
-use std::io::Write;
+#[cfg(feature = "blocking")]
+use std::sync::mpsc::{self, RecvTimeoutError};
 use std::sync::Arc;
-use std::time::Duration;
+#[cfg(feature = "api")]
+use std::sync::OnceLock;
+#[cfg(feature = "blocking")]
+use std::thread;
+use std::time::{Duration, Instant};
 
 use itertools::Itertools;
 use metrics::{Key, Label};
 use metrics_util::registry::{AtomicStorage, Registry};
-use reqwest::header::CONTENT_ENCODING;
+use parking_lot::Mutex;
+#[cfg(feature = "api")]
+use parking_lot::RwLock;
+#[cfg(feature = "api")]
 use reqwest::{blocking, Client};
+#[cfg(feature = "tokio")]
 use tokio::spawn;
+#[cfg(feature = "tokio")]
+use tokio::sync::watch;
+#[cfg(feature = "tokio")]
 use tokio::task::JoinHandle;
+#[cfg(feature = "tokio")]
 use tokio_schedule::{every, Job};
-use tracing::Level;
-use tracing::{debug, enabled, warn};
+use tracing::debug;
+#[cfg(any(feature = "tokio", feature = "blocking"))]
+use tracing::warn;
 
+#[cfg(feature = "api")]
+use crate::api_key::ApiKey;
 use crate::builder::DataDogConfig;
-use crate::data::{DataDogApiPost, DataDogMetric, DataDogSeries};
+#[cfg(feature = "api")]
+use crate::client::ClientConfig;
+use crate::clock::{timestamp, Clock};
+use crate::data::{
+    series_len, DataDogEvent, DataDogMetric, DataDogServiceCheck, DataDogServiceCheckStatus,
+};
+#[cfg(feature = "api")]
+use crate::exporter::api::{api_requests, send_async, send_blocking, Payload, RequestConfig};
+#[cfg(feature = "dogstatsd")]
+use crate::exporter::dogstatsd::{DogStatsD, Invocation};
+#[cfg(feature = "stdout")]
+use crate::exporter::stdout::Output;
+use crate::filter::MetricFilter;
+use crate::report::FlushReport;
+#[cfg(feature = "tokio")]
+use crate::report::FlushStatus;
+#[cfg(any(feature = "api", feature = "stdout", feature = "dogstatsd"))]
+use crate::report::SinkOutcome;
+use crate::stats::{ExporterStats, Telemetry};
+use crate::tags::{FlushTags, TagMergePolicy, TagProvider};
+#[cfg(feature = "testing")]
+use crate::testing::Capture;
 use crate::{Error, Result};
 
-// Size constants from https://docs.datadoghq.com/api/latest/metrics/#submit-metrics
-const MAX_PAYLOAD_BYTES: usize = 3200000;
-const MAX_DECOMPRESSED_PAYLOAD: usize = 62914560;
-
-fn send_blocking(
-    metrics: Vec<DataDogMetric>,
-    gzip: bool,
-    api_host: String,
-    api_key: String,
-    client: blocking::Client,
-) -> Result<(), Error> {
-    if !metrics.is_empty() {
-        let requests = metric_requests(metrics, gzip)?;
-        for request in requests {
-            let mut request = client
-                .post(format!("{}/series", api_host.to_owned()))
-                .header("DD-API-KEY", api_key.to_owned())
-                .body(request);
-            if gzip {
-                request = request.header(CONTENT_ENCODING, "gzip");
-            }
-
-            let response = request.send()?.error_for_status()?;
-            if enabled!(Level::DEBUG) {
-                let status = response.status();
-                let message = response.text()?;
-                debug!(status = %status, message = %message, "Response from DataDog API")
-            }
-        }
-    };
-    Ok(())
-}
-
-async fn send_async(
-    metrics: Vec<DataDogMetric>,
-    gzip: bool,
-    api_host: &String,
-    api_key: &String,
-    client: &Client,
-) -> Result<(), Error> {
-    if !metrics.is_empty() {
-        let requests = metric_requests(metrics, gzip)?;
-        let responses = try_join_all(requests.into_iter().map(|request| async {
-            let mut request = client
-                .post(format!("{}/series", api_host.to_owned()))
-                .header("DD-API-KEY", api_key.to_owned())
-                .body(request);
-            if gzip {
-                request = request.header(CONTENT_ENCODING, "gzip");
-            }
-            let response = request.send().await?.error_for_status()?;
-            let status = response.status();
-            let message = response.text().await?;
-            Ok::<_, reqwest::Error>((status, message))
-        }))
-        .await?;
-
-        if enabled!(Level::DEBUG) {
-            responses.into_iter().for_each(|(status, message)| {
-                debug!(status = %status, message = %message, "Response from DataDog API")
-            });
-        }
-    };
-    Ok(())
-}
-
-fn metric_requests(metrics: Vec<DataDogMetric>, gzip: bool) -> Result<Vec<Vec<u8>>> {
-    let series = metrics
-        .into_iter()
-        .flat_map(DataDogSeries::new)
-        .collect_vec();
-    if gzip {
-        split_and_compress_series(&series)
-    } else {
-        split_series(&series)
-    }
-}
-
-fn split_series(series: &[DataDogSeries]) -> Result<Vec<Vec<u8>>> {
-    let body = serde_json::to_vec(&DataDogApiPost { series })?;
-    if body.len() < MAX_PAYLOAD_BYTES {
-        Ok(vec![body])
-    } else {
-        let (left, right) = series.split_at(series.len() / 2);
-        Ok(split_series(left)?
-            .into_iter()
-            .chain(split_series(right)?.into_iter())
-            .collect_vec())
-    }
-}
+#[cfg(feature = "api")]
+mod api;
+#[cfg(feature = "dogstatsd")]
+pub(crate) mod dogstatsd;
+#[cfg(feature = "stdout")]
+pub(crate) mod stdout;
+#[cfg(feature = "stdout")]
+mod summary;
 
-fn split_and_compress_series(series: &[DataDogSeries]) -> Result<Vec<Vec<u8>>> {
-    fn split(series: &[DataDogSeries]) -> Result<Vec<Vec<u8>>> {
-        let (left, right) = series.split_at(series.len() / 2);
-        Ok(split_and_compress_series(left)?
-            .into_iter()
-            .chain(split_and_compress_series(right)?.into_iter())
-            .collect_vec())
-    }
-
-    let body = serde_json::to_vec(&DataDogApiPost { series })?;
-    if body.len() > MAX_DECOMPRESSED_PAYLOAD {
-        split(series)
-    } else {
-        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
-        encoder.write_all(&serde_json::to_vec(&DataDogApiPost { series })?)?;
-        let compressed = encoder.finish()?;
-        if compressed.len() < MAX_PAYLOAD_BYTES {
-            Ok(vec![compressed])
-        } else {
-            split(series)
-        }
-    }
+/// Metrics, service checks and events collected for a single flush
+pub(crate) struct Pending {
+    pub(crate) metrics: Vec<DataDogMetric>,
+    pub(crate) service_checks: Vec<DataDogServiceCheck>,
+    pub(crate) events: Vec<DataDogEvent>,
+    /// Seconds between flushes, the interval of counts with a single timestamp
+    #[cfg_attr(not(any(feature = "api", feature = "stdout")), allow(dead_code))]
+    pub(crate) flush_interval: Option<i64>,
 }
 
 /// Metric exporter
 pub struct DataDogExporter {
     registry: Arc<Registry<Key, AtomicStorage>>,
-    write_to_stdout: bool,
+    #[cfg(feature = "stdout")]
+    stdout: Option<Output>,
+    #[cfg(feature = "api")]
     write_to_api: bool,
-    api_host: String,
+    #[cfg(feature = "api")]
     api_client: Option<Client>,
-    api_key: Option<String>,
+    #[cfg(feature = "dogstatsd")]
+    dogstatsd: Option<DogStatsD>,
+    #[cfg(feature = "dogstatsd")]
+    invocation: Option<Arc<Invocation>>,
+    #[cfg(feature = "testing")]
+    capture: Option<Capture>,
     tags: Vec<Label>,
-    gzip: bool,
+    tag_providers: Vec<Box<dyn TagProvider>>,
+    tag_merge_policy: TagMergePolicy,
+    filter: MetricFilter,
+    clock: Arc<dyn Clock>,
+    align_timestamps: Option<Duration>,
+    flush_interval: Option<Duration>,
+    #[cfg(feature = "api")]
+    request_config: RequestConfig,
+    #[cfg(feature = "api")]
+    max_in_flight: usize,
+    hostname: Option<String>,
+    service_checks: Mutex<Vec<DataDogServiceCheck>>,
+    events: Mutex<Vec<DataDogEvent>>,
+    telemetry: Arc<Telemetry>,
+    #[cfg(feature = "api")]
+    client_config: ClientConfig,
+    #[cfg(feature = "api")]
+    blocking_client: OnceLock<blocking::Client>,
 }
 
 impl DataDogExporter {
     pub(crate) fn new(
         registry: Arc<Registry<Key, AtomicStorage>>,
-        client: Option<Client>,
+        #[cfg(feature = "api")] client: Option<Client>,
         config: DataDogConfig,
     ) -> Self {
         DataDogExporter {
+            telemetry: Arc::new(Telemetry::new(
+                registry.clone(),
+                config.filter.clone(),
+                config.self_telemetry,
+            )),
             registry,
-            write_to_stdout: config.write_to_stdout,
+            #[cfg(feature = "stdout")]
+            stdout: config.stdout,
+            #[cfg(feature = "api")]
             write_to_api: config.write_to_api,
-            api_host: config.api_host,
+            #[cfg(feature = "api")]
             api_client: client,
-            api_key: config.api_key,
+            #[cfg(feature = "dogstatsd")]
+            dogstatsd: config.dogstatsd,
+            #[cfg(feature = "dogstatsd")]
+            invocation: config.invocation,
+            #[cfg(feature = "testing")]
+            capture: config.capture,
             tags: config.tags,
-            gzip: config.gzip,
+            tag_providers: config.tag_providers,
+            tag_merge_policy: config.tag_merge_policy,
+            filter: config.filter,
+            clock: config.clock,
+            align_timestamps: config.align_timestamps,
+            flush_interval: config.align_timestamps,
+            #[cfg(feature = "api")]
+            request_config: RequestConfig {
+                api_host: config.api_host,
+                api_key: Arc::new(RwLock::new(config.api_key)),
+                api_key_provider: config.api_key_provider,
+                compression: config.compression,
+                headers: config.headers,
+            },
+            #[cfg(feature = "api")]
+            max_in_flight: config.max_in_flight,
+            hostname: config.hostname,
+            service_checks: Mutex::new(vec![]),
+            events: Mutex::new(vec![]),
+            #[cfg(feature = "api")]
+            client_config: config.client_config,
+            #[cfg(feature = "api")]
+            blocking_client: config
+                .blocking_client
+                .map(OnceLock::from)
+                .unwrap_or_default(),
         }
     }
 
+    /// Queue an event to be sent on the next flush
+    pub fn event(&self, event: DataDogEvent) {
+        self.events
+            .lock()
+            .push(event.with_defaults(self.hostname.clone(), self.clock.now().timestamp()));
+    }
+
+    /// Queue a service check to be sent on the next flush
+    pub fn service_check(
+        &self,
+        name: &str,
+        status: DataDogServiceCheckStatus,
+        tags: Vec<(String, String)>,
+        message: Option<String>,
+    ) {
+        let tags = tags.iter().map(Label::from).collect_vec();
+        self.service_checks.lock().push(DataDogServiceCheck::new(
+            name.to_string(),
+            status,
+            &tags,
+            message,
+            self.hostname.clone(),
+            self.clock.now().timestamp(),
+        ));
+    }
+
     /// Write metrics every [`Duration`]
-    pub fn schedule(self, interval: Duration) -> (Arc<Self>, JoinHandle<()>) {
+    ///
+    /// Returns [`Error::Config`] unless `interval` is a whole number of seconds, at least one
+    #[cfg(feature = "tokio")]
+    pub fn schedule(self, interval: Duration) -> Result<(Arc<Self>, JoinHandle<()>)> {
+        let (exporter, scheduled, _) = self.schedule_with_status(interval)?;
+        Ok((exporter, scheduled))
+    }
+
+    /// Write metrics every [`Duration`], publishing a [`FlushStatus`] after each flush
+    ///
+    /// Returns [`Error::Config`] unless `interval` is a whole number of seconds, at least one
+    #[cfg(feature = "tokio")]
+    pub fn schedule_with_status(
+        mut self,
+        interval: Duration,
+    ) -> Result<(Arc<Self>, JoinHandle<()>, watch::Receiver<FlushStatus>)> {
+        if interval.as_secs() == 0 || interval.subsec_nanos() != 0 {
+            return Err(Error::Config(vec![format!(
+                "flush interval {:?} is not a whole number of seconds, at least one",
+                interval
+            )]));
+        }
+        self.flush_interval = Some(interval);
         let exporter = Arc::new(self);
         let scheduled_exporter = exporter.clone();
+        let (sender, receiver) = watch::channel(FlushStatus::default());
+        let sender = Arc::new(sender);
         let every = every(interval.as_secs() as u32).seconds().perform(move || {
             let exporter = scheduled_exporter.clone();
+            let sender = sender.clone();
             async move {
-                let result = exporter.clone().flush().await;
-                if let Err(e) = result {
-                    warn!(error = ?e, "Failed to flush metrics");
+                let report = exporter.flush_report().await;
+                if !report.is_success() {
+                    warn!(error = %report, "Failed to flush metrics");
                 }
+                sender.send_modify(|status| status.update(report));
             }
         });
-        (exporter, spawn(every))
+        Ok((exporter, spawn(every), receiver))
     }
 
     /// Collect metrics
     ///
     /// Note: This will clear histogram observations    
     pub fn collect(&self) -> Vec<DataDogMetric> {
+        self.collect_with(&self.flush_tags())
+    }
+
+    fn flush_tags(&self) -> FlushTags {
+        FlushTags::new(&self.tags, &self.tag_providers, self.tag_merge_policy)
+    }
+
+    fn collect_with(&self, tags: &FlushTags) -> Vec<DataDogMetric> {
+        let timestamp = timestamp(&*self.clock, self.align_timestamps);
         let counters = self
             .registry
             .get_counter_handles()
             .into_iter()
-            .group_by(|(k, _)| k.clone())
+            .chunk_by(|(k, _)| k.clone())
             .into_iter()
             .map(|(key, values)| {
                 DataDogMetric::from_counter(
                     key,
                     values.into_iter().map(|(_, v)| v).collect_vec(),
-                    &self.tags,
+                    timestamp,
+                    tags,
                 )
             })
             .collect_vec();
@@ -206,13 +267,14 @@ impl DataDogExporter {
             .registry
             .get_gauge_handles()
             .into_iter()
-            .group_by(|(k, _)| k.clone())
+            .chunk_by(|(k, _)| k.clone())
             .into_iter()
             .map(|(key, values)| {
                 DataDogMetric::from_gauge(
                     key,
                     values.into_iter().map(|(_, v)| v).collect_vec(),
-                    &self.tags,
+                    timestamp,
+                    tags,
                 )
             })
             .collect_vec();
@@ -221,13 +283,14 @@ impl DataDogExporter {
             .registry
             .get_histogram_handles()
             .into_iter()
-            .group_by(|(k, _)| k.clone())
+            .chunk_by(|(k, _)| k.clone())
             .into_iter()
             .map(|(key, values)| {
                 DataDogMetric::from_histogram(
                     key,
                     values.into_iter().map(|(_, v)| v).collect_vec(),
-                    &self.tags,
+                    timestamp,
+                    tags,
                 )
             })
             .collect_vec();
@@ -236,69 +299,329 @@ impl DataDogExporter {
 
         counters
             .into_iter()
-            .chain(gauges.into_iter())
-            .chain(histograms.into_iter())
+            .chain(gauges)
+            .chain(histograms)
             .collect_vec()
     }
 
-    /// Flush metrics
-    pub async fn flush(&self) -> Result<()> {
-        let metrics: Vec<DataDogMetric> = self.collect();
-        debug!("Flushing {} metrics", metrics.len());
+    /// Filter of the recorder, to change which metrics are recorded at runtime
+    pub fn filter(&self) -> &MetricFilter {
+        &self.filter
+    }
+
+    /// Snapshot of the exporter's own telemetry
+    pub fn stats(&self) -> ExporterStats {
+        self.telemetry.stats()
+    }
 
-        if self.write_to_stdout {
-            self.write_to_stdout(metrics.as_slice())?;
+    /// Replace the API key used from the next flush, e.g. after rotating it
+    ///
+    /// Has no effect when the key comes from [`crate::DataDogBuilder::api_key_provider`]
+    #[cfg(feature = "api")]
+    pub fn set_api_key(&self, api_key: Option<ApiKey>) {
+        *self.request_config.api_key.write() = api_key;
+    }
+
+    /// Flush metrics, queued service checks and events
+    ///
+    /// Returns [`Error::Flush`] with the [`FlushReport`] if any sink failed
+    pub async fn flush(&self) -> Result<FlushReport> {
+        let report = self.flush_report().await;
+        if report.is_success() {
+            Ok(report)
+        } else {
+            Err(Error::Flush(Box::new(report)))
         }
+    }
 
+    #[cfg_attr(not(feature = "api"), allow(unused_mut, unused_variables))]
+    async fn flush_report(&self) -> FlushReport {
+        let (start, pending, mut report) = self.start_flush();
+        #[cfg(feature = "api")]
         if self.write_to_api {
-            self.write_to_api(metrics).await?;
+            let result = self.write_to_api(pending, &mut report).await;
+            report.api = Some(SinkOutcome::from_result(&result));
+        }
+        self.finish_flush(start, report)
+    }
+
+    /// Flush metrics, queued service checks and events without an async runtime
+    ///
+    /// Returns [`Error::Flush`] with the [`FlushReport`] if any sink failed.
+    /// Must not be called from within an async runtime.
+    #[cfg(feature = "blocking")]
+    pub fn flush_blocking(&self) -> Result<FlushReport> {
+        let report = self.flush_report_blocking();
+        if report.is_success() {
+            Ok(report)
+        } else {
+            Err(Error::Flush(Box::new(report)))
         }
+    }
 
-        Ok(())
+    #[cfg(feature = "blocking")]
+    #[cfg_attr(not(feature = "api"), allow(unused_mut, unused_variables))]
+    fn flush_report_blocking(&self) -> FlushReport {
+        let (start, pending, mut report) = self.start_flush();
+        #[cfg(feature = "api")]
+        if self.write_to_api {
+            let result = self.write_to_api_blocking(pending, &mut report);
+            report.api = Some(SinkOutcome::from_result(&result));
+        }
+        self.finish_flush(start, report)
     }
 
-    fn write_to_stdout(&self, metrics: &[DataDogMetric]) -> Result<()> {
-        for metric in metrics {
-            for m in metric.to_metric_lines() {
-                println!("{}", serde_json::to_string(&m)?)
+    /// Flush synchronously at the end of a Lambda invocation, without an async runtime
+    ///
+    /// With [`crate::DataDogBuilder::lambda_extension`] the metrics are tagged with `request_id`, the
+    /// invocation's AWS request id, and after the first invocation with `cold_start:false`.
+    /// Returns [`Error::Flush`] with the [`FlushReport`] if any sink failed.
+    #[cfg(feature = "dogstatsd")]
+    pub fn end_invocation(&self, request_id: &str) -> Result<FlushReport> {
+        if let Some(invocation) = &self.invocation {
+            invocation.start(request_id);
+        }
+        #[cfg(feature = "blocking")]
+        let report = self.flush_report_blocking();
+        // Building validates that the Lambda Extension isn't combined with the API
+        #[cfg(not(feature = "blocking"))]
+        let report = {
+            #[cfg_attr(not(feature = "api"), allow(unused_mut))]
+            let (start, _, mut report) = self.start_flush();
+            #[cfg(feature = "api")]
+            if self.write_to_api {
+                report.api = Some(SinkOutcome::Failed(
+                    "writing to the API at the end of an invocation requires the `blocking` feature"
+                        .to_string(),
+                ));
             }
+            self.finish_flush(start, report)
+        };
+        if let Some(invocation) = &self.invocation {
+            invocation.end();
+        }
+        if report.is_success() {
+            Ok(report)
+        } else {
+            Err(Error::Flush(Box::new(report)))
+        }
+    }
+
+    /// Write metrics every [`Duration`] from a background thread
+    ///
+    /// Returns [`Error::Config`] if `interval` is zero
+    #[cfg(feature = "blocking")]
+    pub fn schedule_blocking(mut self, interval: Duration) -> Result<BlockingScheduler> {
+        if interval.is_zero() {
+            return Err(Error::Config(vec!["flush interval is zero".to_string()]));
+        }
+        self.flush_interval = Some(interval);
+        Ok(BlockingScheduler::new(Arc::new(self), interval))
+    }
+
+    #[cfg_attr(not(any(feature = "stdout", feature = "dogstatsd")), allow(unused_mut))]
+    fn start_flush(&self) -> (Instant, Pending, FlushReport) {
+        let start = Instant::now();
+        let pending = self.take_pending();
+        debug!(
+            "Flushing {} metrics, {} service checks and {} events",
+            pending.metrics.len(),
+            pending.service_checks.len(),
+            pending.events.len()
+        );
+
+        let (series_collected, points_collected) = series_len(&pending.metrics);
+        let mut report = FlushReport {
+            series_collected,
+            points_collected,
+            ..FlushReport::default()
+        };
+
+        #[cfg(feature = "testing")]
+        if let Some(capture) = &self.capture {
+            capture.record(&pending.metrics);
+        }
+        #[cfg(feature = "stdout")]
+        if let Some(stdout) = &self.stdout {
+            let result = stdout.write(&pending);
+            report.stdout = Some(SinkOutcome::from_result(&result));
         }
-        Ok(())
+        #[cfg(feature = "dogstatsd")]
+        if let Some(dogstatsd) = &self.dogstatsd {
+            let result = dogstatsd.send(&pending);
+            report.dogstatsd = Some(SinkOutcome::from_result(&result));
+        }
+        (start, pending, report)
     }
 
-    async fn write_to_api(&self, metrics: Vec<DataDogMetric>) -> Result<(), Error> {
-        send_async(
-            metrics,
-            self.gzip,
-            &self.api_host,
-            self.api_key.as_ref().unwrap(),
+    fn finish_flush(&self, start: Instant, report: FlushReport) -> FlushReport {
+        self.telemetry.record_flush(start.elapsed());
+        report
+    }
+
+    fn take_pending(&self) -> Pending {
+        let tags = self.flush_tags();
+        Pending {
+            metrics: self.collect_with(&tags),
+            service_checks: std::mem::take(&mut *self.service_checks.lock())
+                .into_iter()
+                .map(|check| check.with_flush_tags(&tags))
+                .collect(),
+            events: std::mem::take(&mut *self.events.lock())
+                .into_iter()
+                .map(|event| event.with_flush_tags(&tags))
+                .collect(),
+            flush_interval: self
+                .flush_interval
+                .map(|interval| interval.as_secs() as i64)
+                .filter(|&secs| secs > 0),
+        }
+    }
+
+    #[cfg(feature = "api")]
+    fn api_requests(
+        &self,
+        pending: Pending,
+        report: &mut FlushReport,
+    ) -> Result<Vec<(String, Payload)>> {
+        let requests = api_requests(pending, &self.request_config)?;
+        report.batches = requests.len();
+        report.compressed_bytes = requests.iter().map(|(_, p)| p.body.len()).sum();
+        Ok(requests)
+    }
+
+    #[cfg(feature = "api")]
+    async fn write_to_api(&self, pending: Pending, report: &mut FlushReport) -> Result<(), Error> {
+        let requests = self.api_requests(pending, report)?;
+        let statuses = Mutex::new(vec![]);
+        let result = send_async(
+            requests,
+            &self.request_config,
             self.api_client.as_ref().unwrap(),
+            &self.telemetry,
+            &statuses,
+            self.max_in_flight,
         )
-        .await
+        .await;
+        report.statuses = statuses.into_inner();
+        result
+    }
+
+    #[cfg(all(feature = "api", feature = "blocking"))]
+    fn blocking_client(&self) -> Result<&blocking::Client> {
+        match self.blocking_client.get() {
+            Some(client) => Ok(client),
+            None => {
+                let client = self.client_config.blocking_client()?;
+                Ok(self.blocking_client.get_or_init(|| client))
+            }
+        }
+    }
+
+    #[cfg(all(feature = "api", feature = "blocking"))]
+    fn write_to_api_blocking(&self, pending: Pending, report: &mut FlushReport) -> Result<()> {
+        let requests = self.api_requests(pending, report)?;
+        let statuses = Mutex::new(vec![]);
+        let result = send_blocking(
+            requests,
+            &self.request_config,
+            self.blocking_client()?,
+            &self.telemetry,
+            &statuses,
+        );
+        report.statuses = statuses.into_inner();
+        result
+    }
+}
+
+/// Background thread flushing a [`DataDogExporter`] on an interval
+///
+/// The thread is stopped and joined when the scheduler is dropped
+#[cfg(feature = "blocking")]
+pub struct BlockingScheduler {
+    exporter: Arc<DataDogExporter>,
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+#[cfg(feature = "blocking")]
+impl BlockingScheduler {
+    fn new(exporter: Arc<DataDogExporter>, interval: Duration) -> Self {
+        let (stop, stopped) = mpsc::channel();
+        let scheduled_exporter = exporter.clone();
+        let thread = thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                let report = scheduled_exporter.flush_report_blocking();
+                if !report.is_success() {
+                    warn!(error = %report, "Failed to flush metrics");
+                }
+            }
+        });
+        BlockingScheduler {
+            exporter,
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+
+    /// Scheduled exporter
+    pub fn exporter(&self) -> &Arc<DataDogExporter> {
+        &self.exporter
+    }
+
+    /// Stop the background thread and wait for it to finish
+    pub fn shutdown(self) {
+        drop(self)
+    }
+}
+
+#[cfg(feature = "blocking")]
+impl Drop for BlockingScheduler {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                eprintln!("Failed to join scheduler thread");
+            }
+        }
     }
 }
 
 impl Drop for DataDogExporter {
     fn drop(&mut self) {
-        let metrics = self.collect();
-        if self.write_to_stdout {
-            if let Err(e) = self.write_to_stdout(metrics.as_slice()) {
+        #[cfg_attr(not(feature = "api"), allow(unused_variables))]
+        let pending = self.take_pending();
+        #[cfg(feature = "stdout")]
+        if let Some(stdout) = &self.stdout {
+            if let Err(e) = stdout.write(&pending) {
                 eprintln!("Failed to flush to stdout: {}", e)
             };
         }
+        #[cfg(feature = "dogstatsd")]
+        if let Some(dogstatsd) = &self.dogstatsd {
+            if let Err(e) = dogstatsd.send(&pending) {
+                eprintln!("Failed to flush to DogStatsD: {}", e)
+            };
+        }
 
+        #[cfg(feature = "api")]
         if self.write_to_api {
-            let host = self.api_host.to_string();
-            let api_key = self.api_key.as_ref().unwrap().to_string();
-            let compression = self.gzip;
+            let request_config = self.request_config.clone();
+            let telemetry = self.telemetry.clone();
+            let client = self.blocking_client.get().cloned();
+            let client_config = self.client_config.clone();
             // reqwest::blocking can't run in existing runtime
             let joined = std::thread::spawn(move || {
+                let client = match client {
+                    Some(client) => client,
+                    None => client_config.blocking_client()?,
+                };
                 send_blocking(
-                    metrics,
-                    compression,
-                    host,
-                    api_key,
-                    blocking::Client::default(),
+                    api_requests(pending, &request_config)?,
+                    &request_config,
+                    &client,
+                    &telemetry,
+                    &Mutex::new(vec![]),
                 )
             })
             .join();
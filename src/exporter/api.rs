@@ -0,0 +1,307 @@
+//! DataDog HTTP API transport
+
+use futures::{stream, StreamExt};
+use std::sync::Arc;
+use std::time::Instant;
+
+use parking_lot::{Mutex, RwLock};
+use reqwest::header::{HeaderMap, HeaderValue, CONTENT_ENCODING};
+use reqwest::{blocking, Client};
+use tracing::Level;
+use tracing::{debug, enabled};
+
+use crate::api_key::{ApiKey, ApiKeyProvider};
+use crate::compression::{Compression, Encoder};
+use crate::data::{DataDogMetric, DataDogSeries};
+use crate::exporter::Pending;
+use crate::stats::Telemetry;
+use crate::{Error, Result};
+
+// Size constants from https://docs.datadoghq.com/api/latest/metrics/#submit-metrics
+const MAX_PAYLOAD_BYTES: usize = 3200000;
+const MAX_DECOMPRESSED_PAYLOAD: usize = 62914560;
+// Uncompressed bytes written between flushes measuring the compressed size
+const FLUSH_BUDGET: usize = 64 * 1024;
+const DD_API_KEY: &str = "DD-API-KEY";
+
+/// Settings applied to every request to the DataDog API
+#[derive(Clone)]
+pub(crate) struct RequestConfig {
+    pub(crate) api_host: String,
+    pub(crate) api_key: Arc<RwLock<Option<ApiKey>>>,
+    pub(crate) api_key_provider: Option<ApiKeyProvider>,
+    pub(crate) compression: Compression,
+    pub(crate) headers: HeaderMap,
+}
+
+impl RequestConfig {
+    /// Current API key, from the provider when set
+    fn api_key(&self) -> Option<ApiKey> {
+        match &self.api_key_provider {
+            Some(provider) => provider(),
+            None => self.api_key.read().clone(),
+        }
+    }
+
+    /// Headers for all requests of a flush
+    fn request_headers(&self) -> Result<HeaderMap> {
+        let mut headers = self.headers.clone();
+        if let Some(api_key) = self.api_key() {
+            let mut value =
+                HeaderValue::from_str(api_key.expose()).map_err(|_| Error::InvalidApiKey)?;
+            value.set_sensitive(true);
+            headers.insert(DD_API_KEY, value);
+        }
+        if let Some(encoding) = self.compression.content_encoding() {
+            headers.insert(CONTENT_ENCODING, HeaderValue::from_static(encoding));
+        }
+        Ok(headers)
+    }
+}
+
+/// Request body for the DataDog API
+pub(crate) struct Payload {
+    pub(crate) body: Vec<u8>,
+    uncompressed_bytes: usize,
+    series: usize,
+    points: usize,
+}
+
+impl Payload {
+    fn new(body: Vec<u8>, compression: Compression) -> Result<Self> {
+        let uncompressed_bytes = body.len();
+        Ok(Payload {
+            body: compression.compress(&body)?,
+            uncompressed_bytes,
+            series: 0,
+            points: 0,
+        })
+    }
+}
+
+pub(crate) fn api_requests(
+    pending: Pending,
+    config: &RequestConfig,
+) -> Result<Vec<(String, Payload)>> {
+    let (api_host, compression) = (&config.api_host, config.compression);
+    let mut requests = vec![];
+    if !pending.metrics.is_empty() {
+        let series_url = format!("{}/series", api_host);
+        requests.extend(
+            metric_requests(pending.metrics, pending.flush_interval, compression)?
+                .into_iter()
+                .map(|payload| (series_url.to_owned(), payload)),
+        );
+    }
+    if !pending.service_checks.is_empty() {
+        let body = serde_json::to_vec(&pending.service_checks)?;
+        requests.push((
+            format!("{}/check_run", api_host),
+            Payload::new(body, compression)?,
+        ));
+    }
+    // The events API only accepts a single event per request
+    for event in pending.events {
+        let body = serde_json::to_vec(&event)?;
+        requests.push((
+            format!("{}/events", api_host),
+            Payload::new(body, compression)?,
+        ));
+    }
+    Ok(requests)
+}
+
+pub(crate) fn send_blocking(
+    requests: Vec<(String, Payload)>,
+    config: &RequestConfig,
+    client: &blocking::Client,
+    telemetry: &Telemetry,
+    statuses: &Mutex<Vec<u16>>,
+) -> Result<(), Error> {
+    let total = requests.len();
+    let headers = config.request_headers()?;
+    let mut errors = vec![];
+    for (url, payload) in requests {
+        telemetry.record_payload(payload.uncompressed_bytes, payload.body.len());
+        let request = client.post(url).headers(headers.clone()).body(payload.body);
+
+        let start = Instant::now();
+        let response = request.send().and_then(|r| {
+            statuses.lock().push(r.status().as_u16());
+            r.error_for_status()
+        });
+        telemetry.record_response(
+            start.elapsed(),
+            response.is_ok(),
+            payload.series,
+            payload.points,
+        );
+        match response {
+            Ok(response) if enabled!(Level::DEBUG) => {
+                let status = response.status();
+                let message = response.text().unwrap_or_default();
+                debug!(status = %status, message = %message, "Response from DataDog API")
+            }
+            Ok(_) => (),
+            Err(e) => errors.push(e),
+        }
+    }
+    batch_result(total, errors)
+}
+
+pub(crate) async fn send_async(
+    requests: Vec<(String, Payload)>,
+    config: &RequestConfig,
+    client: &Client,
+    telemetry: &Telemetry,
+    statuses: &Mutex<Vec<u16>>,
+    max_in_flight: usize,
+) -> Result<(), Error> {
+    let total = requests.len();
+    let headers = &config.request_headers()?;
+    let responses = stream::iter(requests)
+        .map(|(url, payload)| async move {
+            telemetry.record_payload(payload.uncompressed_bytes, payload.body.len());
+            let request = client.post(url).headers(headers.clone()).body(payload.body);
+            let start = Instant::now();
+            let response = match request.send().await {
+                Ok(r) => {
+                    statuses.lock().push(r.status().as_u16());
+                    r.error_for_status()
+                }
+                Err(e) => Err(e),
+            };
+            telemetry.record_response(
+                start.elapsed(),
+                response.is_ok(),
+                payload.series,
+                payload.points,
+            );
+            let response = response?;
+            let status = response.status();
+            let message = response.text().await.unwrap_or_default();
+            Ok::<_, reqwest::Error>((status, message))
+        })
+        .buffer_unordered(max_in_flight.max(1))
+        .collect::<Vec<_>>()
+        .await;
+
+    let mut errors = vec![];
+    for response in responses {
+        match response {
+            Ok((status, message)) => {
+                debug!(status = %status, message = %message, "Response from DataDog API")
+            }
+            Err(e) => errors.push(e),
+        }
+    }
+    batch_result(total, errors)
+}
+
+fn batch_result(total: usize, errors: Vec<reqwest::Error>) -> Result<()> {
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::Batches { total, errors })
+    }
+}
+
+fn metric_requests(
+    metrics: Vec<DataDogMetric>,
+    flush_interval: Option<i64>,
+    compression: Compression,
+) -> Result<Vec<Payload>> {
+    let mut payloads = vec![];
+    let mut writer = PayloadWriter::new(compression)?;
+    for series in DataDogSeries::group(metrics, flush_interval) {
+        let item = serde_json::to_vec(&series)?;
+        if !writer.fits(item.len())? {
+            payloads.push(writer.finish()?);
+            writer = PayloadWriter::new(compression)?;
+        }
+        writer.write(&item, series.points.len())?;
+    }
+    if writer.series > 0 {
+        payloads.push(writer.finish()?);
+    }
+    Ok(payloads)
+}
+
+/// Writes series into a compressed `{"series":[...]}` body, one at a time
+struct PayloadWriter {
+    encoder: Encoder,
+    uncompressed_bytes: usize,
+    /// Bytes written since the encoder was last flushed
+    unflushed_bytes: usize,
+    series: usize,
+    points: usize,
+}
+
+impl PayloadWriter {
+    const PREFIX: &'static [u8] = b"{\"series\":[";
+    const SUFFIX: &'static [u8] = b"]}";
+
+    fn new(compression: Compression) -> Result<Self> {
+        let mut encoder = Encoder::new(compression)?;
+        encoder.write_all(Self::PREFIX)?;
+        Ok(PayloadWriter {
+            encoder,
+            uncompressed_bytes: Self::PREFIX.len(),
+            unflushed_bytes: Self::PREFIX.len(),
+            series: 0,
+            points: 0,
+        })
+    }
+
+    /// Whether a series of `len` bytes can be added without exceeding the payload limits
+    fn fits(&mut self, len: usize) -> Result<bool> {
+        if self.series == 0 {
+            return Ok(true);
+        }
+        // separator and closing suffix
+        let len = len + 1 + Self::SUFFIX.len();
+        if self.uncompressed_bytes + len > MAX_DECOMPRESSED_PAYLOAD {
+            return Ok(false);
+        }
+        if !self.encoder.is_compressed() {
+            return Ok(self.uncompressed_bytes + len < MAX_PAYLOAD_BYTES);
+        }
+        let estimate =
+            |w: &Self| w.encoder.encoded_len() + w.encoder.bound(w.unflushed_bytes + len);
+        if estimate(self) < MAX_PAYLOAD_BYTES {
+            return Ok(true);
+        }
+        // the estimate is pessimistic, flush to get the exact compressed size, but only once
+        // enough was written since the last flush, as each flush costs compression
+        if self.unflushed_bytes < FLUSH_BUDGET {
+            return Ok(false);
+        }
+        self.encoder.flush()?;
+        self.unflushed_bytes = 0;
+        Ok(estimate(self) < MAX_PAYLOAD_BYTES)
+    }
+
+    fn write(&mut self, item: &[u8], points: usize) -> Result<()> {
+        if self.series > 0 {
+            self.encoder.write_all(b",")?;
+            self.uncompressed_bytes += 1;
+            self.unflushed_bytes += 1;
+        }
+        self.encoder.write_all(item)?;
+        self.uncompressed_bytes += item.len();
+        self.unflushed_bytes += item.len();
+        self.series += 1;
+        self.points += points;
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<Payload> {
+        self.encoder.write_all(Self::SUFFIX)?;
+        Ok(Payload {
+            body: self.encoder.finish()?,
+            uncompressed_bytes: self.uncompressed_bytes + Self::SUFFIX.len(),
+            series: self.series,
+            points: self.points,
+        })
+    }
+}
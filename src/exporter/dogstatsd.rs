@@ -0,0 +1,107 @@
+//! DogStatsD UDP transport, e.g. to the Datadog Agent or the Datadog Lambda Extension
+
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use parking_lot::Mutex;
+
+use crate::exporter::Pending;
+use crate::Result;
+
+// Default buffer size of the Agent and the Lambda Extension
+const MAX_DATAGRAM_BYTES: usize = 8192;
+
+/// Address of the Datadog Lambda Extension's DogStatsD listener
+pub(crate) const LAMBDA_EXTENSION_ADDR: &str = "127.0.0.1:8125";
+
+/// Whether `addr` looks like `host:port`, without resolving it
+pub(crate) fn is_valid_addr(addr: &str) -> bool {
+    addr.rsplit_once(':')
+        .is_some_and(|(host, port)| !host.is_empty() && port.parse::<u16>().is_ok())
+}
+
+/// The Lambda invocation being flushed, tagged on its metrics
+pub(crate) struct Invocation {
+    cold_start: AtomicBool,
+    request_id: Mutex<Option<String>>,
+}
+
+impl Invocation {
+    pub(crate) fn new() -> Self {
+        Invocation {
+            cold_start: AtomicBool::new(true),
+            request_id: Mutex::new(None),
+        }
+    }
+
+    /// `cold_start` and, while an invocation is flushed, `request_id`
+    pub(crate) fn tags(&self) -> Vec<(String, String)> {
+        let mut tags = vec![(
+            "cold_start".to_string(),
+            self.cold_start.load(Ordering::Relaxed).to_string(),
+        )];
+        if let Some(request_id) = self.request_id.lock().clone() {
+            tags.push(("request_id".to_string(), request_id));
+        }
+        tags
+    }
+
+    pub(crate) fn start(&self, request_id: &str) {
+        *self.request_id.lock() = Some(request_id.to_string());
+    }
+
+    pub(crate) fn end(&self) {
+        *self.request_id.lock() = None;
+        self.cold_start.store(false, Ordering::Relaxed);
+    }
+}
+
+pub(crate) struct DogStatsD {
+    socket: UdpSocket,
+}
+
+impl DogStatsD {
+    pub(crate) fn connect(addr: &str) -> Result<Self> {
+        let target = addr.to_socket_addrs()?.next().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("`{}` doesn't resolve to an address", addr),
+            )
+        })?;
+        let local: SocketAddr = if target.is_ipv4() {
+            ([0, 0, 0, 0], 0).into()
+        } else {
+            ([0u16; 8], 0).into()
+        };
+        let socket = UdpSocket::bind(local)?;
+        socket.connect(target)?;
+        Ok(DogStatsD { socket })
+    }
+
+    /// Sends metrics, service checks and events, packing as many lines as fit in each datagram
+    pub(crate) fn send(&self, pending: &Pending) -> Result<()> {
+        let lines = pending
+            .metrics
+            .iter()
+            .flat_map(|metric| metric.to_dogstatsd())
+            .chain(pending.service_checks.iter().map(|c| c.to_dogstatsd()))
+            .chain(pending.events.iter().map(|e| e.to_dogstatsd()));
+
+        let mut datagram = String::new();
+        for line in lines {
+            if !datagram.is_empty() && datagram.len() + 1 + line.len() > MAX_DATAGRAM_BYTES {
+                self.socket.send(datagram.as_bytes())?;
+                datagram.clear();
+            }
+            if !datagram.is_empty() {
+                datagram.push('\n');
+            }
+            datagram.push_str(&line);
+        }
+        if !datagram.is_empty() {
+            self.socket.send(datagram.as_bytes())?;
+        }
+        Ok(())
+    }
+}
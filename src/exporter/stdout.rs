@@ -0,0 +1,184 @@
+//! Stdout sink, writing to any [`Write`] destination in a selectable format
+
+use std::io::Write;
+
+use parking_lot::Mutex;
+
+use crate::data::{DataDogApiPost, DataDogSeries};
+use crate::exporter::summary::Summary;
+use crate::exporter::Pending;
+use crate::Result;
+
+/// Format written by the stdout sink
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// One compact JSON line (`m`, `v`, `e`, `t`) per point, parsed by the Datadog Forwarder
+    #[default]
+    Lambda,
+    /// One v1 `{"series": [...]}` JSON document per flush
+    Series,
+    /// DogStatsD datagrams, one per line
+    DogStatsD,
+    /// Human readable table of every point
+    Table,
+    /// Metrics aggregated by name and tags across flushes, for local development
+    ///
+    /// Counters show totals and rates, gauges last/min/max and histograms count/p50/p95/p99.
+    Summary,
+}
+
+/// Destination and format of the stdout sink
+pub(crate) struct Output {
+    writer: Mutex<Box<dyn Write + Send>>,
+    format: OutputFormat,
+    summary: Summary,
+}
+
+impl Output {
+    pub(crate) fn new(writer: Box<dyn Write + Send>, format: OutputFormat) -> Self {
+        Output {
+            writer: Mutex::new(writer),
+            format,
+            summary: Summary::default(),
+        }
+    }
+
+    /// Renders the whole flush before writing it at once
+    pub(crate) fn write(&self, pending: &Pending) -> Result<()> {
+        let mut buf = vec![];
+        match self.format {
+            OutputFormat::Lambda => write_lambda(&mut buf, pending)?,
+            OutputFormat::Series => write_series(&mut buf, pending)?,
+            OutputFormat::DogStatsD => write_dogstatsd(&mut buf, pending)?,
+            OutputFormat::Table => write_table(&mut buf, pending)?,
+            OutputFormat::Summary => {
+                self.summary.write(&mut buf, &pending.metrics)?;
+                write_checks_and_events(&mut buf, pending)?;
+            }
+        }
+        let mut writer = self.writer.lock();
+        writer.write_all(&buf)?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+fn write_json_lines<T: serde::Serialize>(buf: &mut Vec<u8>, items: &[T]) -> Result<()> {
+    for item in items {
+        serde_json::to_writer(&mut *buf, item)?;
+        buf.push(b'\n');
+    }
+    Ok(())
+}
+
+fn write_lambda(buf: &mut Vec<u8>, pending: &Pending) -> Result<()> {
+    for metric in &pending.metrics {
+        write_json_lines(buf, &metric.to_metric_lines())?;
+    }
+    write_json_lines(buf, &pending.service_checks)?;
+    write_json_lines(buf, &pending.events)
+}
+
+fn write_series(buf: &mut Vec<u8>, pending: &Pending) -> Result<()> {
+    if !pending.metrics.is_empty() {
+        let series = DataDogSeries::group(pending.metrics.iter().cloned(), pending.flush_interval);
+        serde_json::to_writer(&mut *buf, &DataDogApiPost { series: &series })?;
+        buf.push(b'\n');
+    }
+    write_json_lines(buf, &pending.service_checks)?;
+    write_json_lines(buf, &pending.events)
+}
+
+fn write_dogstatsd(buf: &mut Vec<u8>, pending: &Pending) -> Result<()> {
+    let lines = pending
+        .metrics
+        .iter()
+        .flat_map(|metric| metric.to_dogstatsd())
+        .chain(pending.service_checks.iter().map(|c| c.to_dogstatsd()))
+        .chain(pending.events.iter().map(|e| e.to_dogstatsd()));
+    for line in lines {
+        writeln!(buf, "{}", line)?;
+    }
+    Ok(())
+}
+
+fn write_table(buf: &mut Vec<u8>, pending: &Pending) -> Result<()> {
+    let rows = pending
+        .metrics
+        .iter()
+        .flat_map(|metric| {
+            metric.points.iter().map(|(_, value)| {
+                [
+                    metric.metric.to_string(),
+                    format!("{:?}", metric.metric_type).to_lowercase(),
+                    value.to_string(),
+                    metric.tags.join(","),
+                ]
+            })
+        })
+        .collect::<Vec<_>>();
+    write_rows(buf, ["METRIC", "TYPE", "VALUE", "TAGS"], &rows)?;
+    write_checks_and_events(buf, pending)
+}
+
+fn write_checks_and_events(buf: &mut Vec<u8>, pending: &Pending) -> Result<()> {
+    let rows = pending
+        .service_checks
+        .iter()
+        .map(|check| {
+            [
+                check.check.to_string(),
+                format!("{:?}", check.status).to_lowercase(),
+                check.message.clone().unwrap_or_default(),
+                check.tags.join(","),
+            ]
+        })
+        .collect::<Vec<_>>();
+    write_rows(buf, ["CHECK", "STATUS", "MESSAGE", "TAGS"], &rows)?;
+
+    let rows = pending
+        .events
+        .iter()
+        .map(|event| {
+            [
+                event.title.to_string(),
+                event
+                    .alert_type
+                    .map(|t| t.as_str().to_string())
+                    .unwrap_or_default(),
+                event.text.to_string(),
+                event.tags.join(","),
+            ]
+        })
+        .collect::<Vec<_>>();
+    write_rows(buf, ["EVENT", "ALERT", "TEXT", "TAGS"], &rows)
+}
+
+/// Writes left aligned columns, nothing when there are no rows
+pub(crate) fn write_rows<const N: usize>(
+    buf: &mut Vec<u8>,
+    header: [&str; N],
+    rows: &[[String; N]],
+) -> Result<()> {
+    if rows.is_empty() {
+        return Ok(());
+    }
+    let mut widths = header.map(str::len);
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+    let header = header.map(str::to_string);
+    for row in std::iter::once(&header).chain(rows) {
+        let line = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("  ");
+        writeln!(buf, "{}", line.trim_end())?;
+    }
+    writeln!(buf)?;
+    Ok(())
+}
@@ -0,0 +1,180 @@
+//! Aggregated console summary for local development
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::time::Instant;
+
+use parking_lot::Mutex;
+
+use crate::data::{DataDogMetric, DataDogMetricType, DataDogMetricValue};
+use crate::exporter::stdout::write_rows;
+use crate::Result;
+
+/// Metrics aggregated across flushes, keyed by name and tags
+pub(crate) struct Summary {
+    state: Mutex<State>,
+}
+
+struct State {
+    last_flush: Instant,
+    aggregates: BTreeMap<(String, String), Aggregate>,
+}
+
+enum Aggregate {
+    Counter { total: f64, interval: f64 },
+    Gauge { last: f64, min: f64, max: f64 },
+    Histogram { values: Vec<f64> },
+}
+
+impl Default for Summary {
+    fn default() -> Self {
+        Summary {
+            state: Mutex::new(State {
+                last_flush: Instant::now(),
+                aggregates: BTreeMap::new(),
+            }),
+        }
+    }
+}
+
+impl Summary {
+    /// Adds the flushed metrics and writes counters, gauges and histograms tables
+    ///
+    /// Counters show the total since start and the rate over the last interval,
+    /// histograms only the values recorded in the last interval.
+    pub(crate) fn write(&self, buf: &mut Vec<u8>, metrics: &[DataDogMetric]) -> Result<()> {
+        let mut state = self.state.lock();
+        let elapsed = state.last_flush.elapsed().as_secs_f64();
+        state.last_flush = Instant::now();
+        for aggregate in state.aggregates.values_mut() {
+            match aggregate {
+                Aggregate::Counter { interval, .. } => *interval = 0.0,
+                Aggregate::Histogram { values } => values.clear(),
+                Aggregate::Gauge { .. } => {}
+            }
+        }
+        for metric in metrics {
+            let key = (metric.metric.to_string(), metric.tags.join(","));
+            let points = metric.points.iter().map(|(_, value)| as_f64(value));
+            match metric.metric_type {
+                DataDogMetricType::Count => {
+                    let sum = points.sum::<f64>();
+                    match state.aggregates.get_mut(&key) {
+                        Some(Aggregate::Counter { total, interval }) => {
+                            *total += sum;
+                            *interval += sum;
+                        }
+                        _ => {
+                            let counter = Aggregate::Counter {
+                                total: sum,
+                                interval: sum,
+                            };
+                            state.aggregates.insert(key, counter);
+                        }
+                    }
+                }
+                DataDogMetricType::Gauge => {
+                    for value in points {
+                        match state.aggregates.get_mut(&key) {
+                            Some(Aggregate::Gauge { last, min, max }) => {
+                                *last = value;
+                                *min = min.min(value);
+                                *max = max.max(value);
+                            }
+                            _ => {
+                                let gauge = Aggregate::Gauge {
+                                    last: value,
+                                    min: value,
+                                    max: value,
+                                };
+                                state.aggregates.insert(key.clone(), gauge);
+                            }
+                        }
+                    }
+                }
+                DataDogMetricType::Histogram => match state.aggregates.get_mut(&key) {
+                    Some(Aggregate::Histogram { values }) => values.extend(points),
+                    _ => {
+                        let values = points.collect();
+                        state
+                            .aggregates
+                            .insert(key, Aggregate::Histogram { values });
+                    }
+                },
+            }
+        }
+
+        let mut counters = vec![];
+        let mut gauges = vec![];
+        let mut histograms = vec![];
+        for ((name, tags), aggregate) in &state.aggregates {
+            match aggregate {
+                Aggregate::Counter { total, interval } => {
+                    let rate = if elapsed > 0.0 {
+                        interval / elapsed
+                    } else {
+                        0.0
+                    };
+                    counters.push([
+                        name.to_string(),
+                        tags.to_string(),
+                        number(*total),
+                        number(rate),
+                    ])
+                }
+                Aggregate::Gauge { last, min, max } => gauges.push([
+                    name.to_string(),
+                    tags.to_string(),
+                    number(*last),
+                    number(*min),
+                    number(*max),
+                ]),
+                Aggregate::Histogram { values } => {
+                    let mut sorted = values.clone();
+                    sorted.sort_by(f64::total_cmp);
+                    histograms.push([
+                        name.to_string(),
+                        tags.to_string(),
+                        sorted.len().to_string(),
+                        percentile(&sorted, 0.50),
+                        percentile(&sorted, 0.95),
+                        percentile(&sorted, 0.99),
+                    ])
+                }
+            }
+        }
+
+        writeln!(buf, "--- metrics over the last {:.1}s ---", elapsed)?;
+        write_rows(buf, ["COUNTER", "TAGS", "TOTAL", "RATE/S"], &counters)?;
+        write_rows(buf, ["GAUGE", "TAGS", "LAST", "MIN", "MAX"], &gauges)?;
+        write_rows(
+            buf,
+            ["HISTOGRAM", "TAGS", "COUNT", "P50", "P95", "P99"],
+            &histograms,
+        )
+    }
+}
+
+fn as_f64(value: &DataDogMetricValue) -> f64 {
+    match value {
+        DataDogMetricValue::Float(f) => *f,
+        DataDogMetricValue::Unsigned(u) => *u as f64,
+    }
+}
+
+/// Nearest rank percentile of sorted values
+fn percentile(sorted: &[f64], q: f64) -> String {
+    if sorted.is_empty() {
+        return "-".to_string();
+    }
+    let rank = (q * sorted.len() as f64).ceil() as usize;
+    number(sorted[rank.clamp(1, sorted.len()) - 1])
+}
+
+fn number(value: f64) -> String {
+    if value.fract() == 0.0 {
+        format!("{}", value)
+    } else {
+        format!("{:.3}", value)
+    }
+}
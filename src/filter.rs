@@ -0,0 +1,117 @@
+//! Filtering metrics by target and level
+//!
+use std::cmp::Reverse;
+use std::sync::Arc;
+
+use metrics::{Level, Metadata};
+use parking_lot::RwLock;
+
+use crate::{Error, Result};
+
+/// Minimum level of a directive, [`None`] disables its targets
+type Threshold = Option<Level>;
+
+fn parse_threshold(level: &str) -> Result<Threshold, String> {
+    match level.trim() {
+        "off" | "OFF" => Ok(None),
+        level => Level::try_from(level).map(Some),
+    }
+}
+
+/// Parsed filter directives, e.g. `hyper=off,my_app=debug,info`
+#[derive(Clone, Debug)]
+pub(crate) struct Directives {
+    default: Threshold,
+    // Longest target first, so the most specific directive matches
+    targets: Vec<(String, Threshold)>,
+}
+
+impl Default for Directives {
+    fn default() -> Self {
+        Directives {
+            default: Some(Level::TRACE),
+            targets: vec![],
+        }
+    }
+}
+
+impl Directives {
+    pub(crate) fn set_default(&mut self, threshold: Threshold) {
+        self.default = threshold;
+    }
+
+    pub(crate) fn insert(&mut self, target: String, threshold: Threshold) {
+        self.targets.retain(|(t, _)| *t != target);
+        self.targets.push((target, threshold));
+        self.targets.sort_by_key(|(t, _)| Reverse(t.len()));
+    }
+
+    /// Adds comma separated `target=level`, `target` or `level` directives
+    pub(crate) fn parse(&mut self, directives: &str) -> Result<(), String> {
+        for directive in directives.split(',').map(str::trim) {
+            match directive.split_once('=') {
+                _ if directive.is_empty() => (),
+                Some((target, level)) => {
+                    self.insert(target.trim().to_string(), parse_threshold(level)?)
+                }
+                None => match parse_threshold(directive) {
+                    Ok(threshold) => self.set_default(threshold),
+                    Err(_) => self.insert(directive.to_string(), Some(Level::TRACE)),
+                },
+            }
+        }
+        Ok(())
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        let target = metadata.target();
+        self.targets
+            .iter()
+            .find(|(t, _)| {
+                target
+                    .strip_prefix(t.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+            })
+            .map_or(self.default, |(_, threshold)| *threshold)
+            .is_some_and(|min| *metadata.level() >= min)
+    }
+}
+
+/// Decides which metrics are recorded by their target and level
+///
+/// Clones share the same directives, so changes apply to the recorder from the next time a metric
+/// is registered, at the latest after the next flush.
+#[derive(Clone, Default)]
+pub struct MetricFilter {
+    directives: Arc<RwLock<Directives>>,
+}
+
+impl MetricFilter {
+    pub(crate) fn new(directives: Directives) -> Self {
+        MetricFilter {
+            directives: Arc::new(RwLock::new(directives)),
+        }
+    }
+
+    /// Replace all directives, e.g. `hyper=off,my_app=debug,info`
+    ///
+    /// `target=level` sets the minimum level for a target and the modules below it,
+    /// `level` the minimum level for all other targets and `off` disables them.
+    pub fn reload(&self, directives: &str) -> Result<()> {
+        let mut parsed = Directives::default();
+        parsed
+            .parse(directives)
+            .map_err(|e| Error::Config(vec![e]))?;
+        *self.directives.write() = parsed;
+        Ok(())
+    }
+
+    /// Set the minimum level for targets without a directive
+    pub fn set_min_level(&self, level: Level) {
+        self.directives.write().set_default(Some(level));
+    }
+
+    pub(crate) fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        self.directives.read().enabled(metadata)
+    }
+}
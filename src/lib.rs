@@ -1,25 +1,80 @@
 #![warn(missing_docs)]
 
 //! Exports any metrics to DataDog
+//!
+//! # Features
+//!
+//! - `api` (default): write metrics to the DataDog HTTP API
+//! - `stdout` (default): write metrics to stdout in DataDog JSON format
+//! - `gzip` (default): compress API payloads with gzip
+//! - `deflate`: compress API payloads with zlib deflate
+//! - `zstd`: compress API payloads with zstd
+//! - `tokio` (default): schedule flushes on a tokio runtime
+//! - `rustls` (default) / `native-tls`: TLS backend for the API client
+//! - `blocking`: flush and schedule without an async runtime
+//! - `dogstatsd`: write to a DogStatsD listener, e.g. the Datadog Lambda Extension
+//! - `metrics-021`: record metrics from code still using metrics 0.21
+//! - `testing`: in-memory capture sink and assertion helpers, see [`testing`]
 
 use metrics::SetRecorderError;
 use std::io;
+#[cfg(feature = "tokio")]
 use std::sync::Arc;
+#[cfg(any(feature = "tokio", feature = "blocking"))]
 use std::time::Duration;
 use thiserror::Error;
+#[cfg(feature = "tokio")]
+use tokio::sync::watch;
+#[cfg(feature = "tokio")]
 use tokio::task::JoinHandle;
 
+#[cfg(feature = "api")]
+mod api_key;
+#[cfg(feature = "api")]
+pub use crate::api_key::ApiKey;
 mod builder;
+mod clock;
+pub use crate::clock::{Clock, ManualClock, SystemClock};
+#[cfg(feature = "metrics-021")]
+mod compat;
 pub use crate::builder::DataDogBuilder;
+#[cfg(feature = "api")]
+mod client;
+#[cfg(feature = "api")]
+mod compression;
+#[cfg(feature = "api")]
+pub use crate::compression::Compression;
 pub mod data;
+pub use crate::data::DataDogEvent;
+pub use crate::data::DataDogEventAlertType;
+pub use crate::data::DataDogEventPriority;
 pub use crate::data::DataDogMetric;
 pub use crate::data::DataDogMetricType;
 pub use crate::data::DataDogMetricValue;
+pub use crate::data::DataDogServiceCheckStatus;
 pub use metrics;
+#[cfg(feature = "api")]
+pub use reqwest;
+mod detect;
+pub use crate::detect::Detector;
 pub mod exporter;
+#[cfg(feature = "stdout")]
+pub use crate::exporter::stdout::OutputFormat;
+#[cfg(feature = "blocking")]
+pub use crate::exporter::BlockingScheduler;
 pub use crate::exporter::DataDogExporter;
+mod filter;
+pub use crate::filter::MetricFilter;
 mod recorder;
 pub use crate::recorder::DataDogRecorder;
+mod report;
+pub use crate::report::{FlushReport, FlushStatus, SinkOutcome};
+mod stats;
+pub use crate::stats::ExporterStats;
+mod tags;
+pub use crate::tags::{TagMergePolicy, TagProvider};
+#[cfg(feature = "testing")]
+pub mod testing;
 
 /// Error handling metrics
 #[derive(Error, Debug)]
@@ -28,11 +83,40 @@ pub enum Error {
     #[error("Serialization failed: `{0}`")]
     SerializationError(#[from] serde_json::Error),
     /// Error when interacting with DataDog API
+    #[cfg(feature = "api")]
     #[error("API Request Failed: `{0}`")]
     ApiError(#[from] reqwest::Error),
+    /// One or more requests to the DataDog API failed
+    #[cfg(feature = "api")]
+    #[error("{} of {total} API requests failed: `{}`", .errors.len(), join_errors(.errors))]
+    Batches {
+        /// Number of requests made
+        total: usize,
+        /// Errors of the failed requests
+        errors: Vec<reqwest::Error>,
+    },
+    /// API key can't be sent as a header value
+    #[cfg(feature = "api")]
+    #[error("API key is not a valid header value")]
+    InvalidApiKey,
+    /// Builder configuration is invalid, describes every problem found
+    #[error("Invalid configuration: {}", .0.join("; "))]
+    Config(Vec<String>),
     /// Error compressing or decompressing
     #[error("IO error: `{0}`")]
     IOError(#[from] io::Error),
+    /// One or more sinks failed during a flush
+    #[error("Flush failed: `{0}`")]
+    Flush(Box<FlushReport>),
+}
+
+#[cfg(feature = "api")]
+fn join_errors(errors: &[reqwest::Error]) -> String {
+    errors
+        .iter()
+        .map(|e| e.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
 }
 
 /// [`Ok`] or [`enum@Error`]
@@ -48,18 +132,89 @@ pub struct DataDogHandle {
 
 impl DataDogHandle {
     /// Install [`DataDogRecorder`] and return [`DataDogExporter`]
-    pub fn install(self) -> Result<DataDogExporter, SetRecorderError> {
-        metrics::set_boxed_recorder(Box::new(self.recorder))?;
+    pub fn install(self) -> Result<DataDogExporter, SetRecorderError<DataDogRecorder>> {
+        metrics::set_global_recorder(self.recorder)?;
+        Ok(self.handle)
+    }
+
+    /// Install [`DataDogRecorder`] as the metrics 0.21 recorder and return [`DataDogExporter`]
+    #[cfg(feature = "metrics-021")]
+    pub fn install_metrics_021(self) -> Result<DataDogExporter, metrics_021::SetRecorderError> {
+        metrics_021::set_boxed_recorder(Box::new(self.recorder))?;
         Ok(self.handle)
     }
 
+    /// Flush at the end of a Lambda invocation, see [`DataDogExporter::end_invocation`]
+    #[cfg(feature = "dogstatsd")]
+    pub fn end_invocation(&self, request_id: &str) -> Result<FlushReport> {
+        self.handle.end_invocation(request_id)
+    }
+
+    /// Split into [`DataDogRecorder`] and [`DataDogExporter`] without installing anything
+    pub fn into_parts(self) -> (DataDogRecorder, DataDogExporter) {
+        (self.recorder, self.handle)
+    }
+
+    /// Queue a service check to be sent on the next flush
+    pub fn service_check(
+        &self,
+        name: &str,
+        status: DataDogServiceCheckStatus,
+        tags: Vec<(String, String)>,
+        message: Option<String>,
+    ) {
+        self.handle.service_check(name, status, tags, message)
+    }
+
+    /// Queue an event to be sent on the next flush
+    pub fn event(&self, event: DataDogEvent) {
+        self.handle.event(event)
+    }
+
+    /// Snapshot of the exporter's own telemetry
+    pub fn stats(&self) -> ExporterStats {
+        self.handle.stats()
+    }
+
+    /// Replace the API key used from the next flush, see [`DataDogExporter::set_api_key`]
+    #[cfg(feature = "api")]
+    pub fn set_api_key(&self, api_key: Option<ApiKey>) {
+        self.handle.set_api_key(api_key)
+    }
+
     /// Flush metrics
-    pub async fn flush(&self) -> Result<()> {
+    pub async fn flush(&self) -> Result<FlushReport> {
         self.handle.flush().await
     }
 
+    /// Flush metrics without an async runtime
+    #[cfg(feature = "blocking")]
+    pub fn flush_blocking(&self) -> Result<FlushReport> {
+        self.handle.flush_blocking()
+    }
+
+    /// Write metrics every [`Duration`] from a background thread
+    #[cfg(feature = "blocking")]
+    pub fn schedule_blocking(self, interval: Duration) -> Result<BlockingScheduler> {
+        self.handle.schedule_blocking(interval)
+    }
+
     /// Write metrics every [`Duration`]
-    pub fn schedule(self, interval: Duration) -> (Arc<DataDogExporter>, JoinHandle<()>) {
+    #[cfg(feature = "tokio")]
+    pub fn schedule(self, interval: Duration) -> Result<(Arc<DataDogExporter>, JoinHandle<()>)> {
         self.handle.schedule(interval)
     }
+
+    /// Write metrics every [`Duration`], publishing a [`FlushStatus`] after each flush
+    #[cfg(feature = "tokio")]
+    pub fn schedule_with_status(
+        self,
+        interval: Duration,
+    ) -> Result<(
+        Arc<DataDogExporter>,
+        JoinHandle<()>,
+        watch::Receiver<FlushStatus>,
+    )> {
+        self.handle.schedule_with_status(interval)
+    }
 }
@@ -1,43 +1,59 @@
 use std::sync::Arc;
 
-use metrics::{Counter, Gauge, Histogram, Key, KeyName, Recorder, SharedString, Unit};
+use metrics::{Counter, Gauge, Histogram, Key, KeyName, Metadata, Recorder, SharedString, Unit};
 use metrics_util::registry::{AtomicStorage, Registry};
 
+use crate::filter::MetricFilter;
+use crate::tags::normalize_key;
+
 /// Metric recorder
+///
+/// Cloning is cheap and clones record into the same registry, so the recorder can be wrapped in
+/// `metrics_util` layers or added to a fanout next to other exporters.
+#[derive(Clone)]
 pub struct DataDogRecorder {
-    registry: Arc<Registry<Key, AtomicStorage>>,
+    pub(crate) registry: Arc<Registry<Key, AtomicStorage>>,
+    filter: MetricFilter,
 }
 
 impl DataDogRecorder {
-    pub(crate) fn new(registry: Arc<Registry<Key, AtomicStorage>>) -> Self {
-        DataDogRecorder { registry }
+    pub(crate) fn new(registry: Arc<Registry<Key, AtomicStorage>>, filter: MetricFilter) -> Self {
+        DataDogRecorder { registry, filter }
     }
 }
 
+// Descriptions aren't sent to DataDog
 impl Recorder for DataDogRecorder {
-    fn describe_counter(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {
-        unimplemented!()
-    }
+    fn describe_counter(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {}
 
-    fn describe_gauge(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {
-        unimplemented!()
-    }
+    fn describe_gauge(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {}
 
-    fn describe_histogram(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {
-        unimplemented!()
-    }
+    fn describe_histogram(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {}
 
-    fn register_counter(&self, key: &Key) -> Counter {
+    fn register_counter(&self, key: &Key, metadata: &Metadata<'_>) -> Counter {
+        if !self.filter.enabled(metadata) {
+            return Counter::noop();
+        }
+        let normalized = normalize_key(key);
         self.registry
-            .get_or_create_counter(key, |c| c.clone().into())
+            .get_or_create_counter(normalized.as_ref().unwrap_or(key), |c| c.clone().into())
     }
 
-    fn register_gauge(&self, key: &Key) -> Gauge {
-        self.registry.get_or_create_gauge(key, |c| c.clone().into())
+    fn register_gauge(&self, key: &Key, metadata: &Metadata<'_>) -> Gauge {
+        if !self.filter.enabled(metadata) {
+            return Gauge::noop();
+        }
+        let normalized = normalize_key(key);
+        self.registry
+            .get_or_create_gauge(normalized.as_ref().unwrap_or(key), |c| c.clone().into())
     }
 
-    fn register_histogram(&self, key: &Key) -> Histogram {
+    fn register_histogram(&self, key: &Key, metadata: &Metadata<'_>) -> Histogram {
+        if !self.filter.enabled(metadata) {
+            return Histogram::noop();
+        }
+        let normalized = normalize_key(key);
         self.registry
-            .get_or_create_histogram(key, |c| c.clone().into())
+            .get_or_create_histogram(normalized.as_ref().unwrap_or(key), |c| c.clone().into())
     }
 }
@@ -0,0 +1,109 @@
+//! Flush reporting
+//!
+use std::fmt::{Display, Formatter};
+
+use chrono::{DateTime, Utc};
+
+/// Outcome of writing to a single sink
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SinkOutcome {
+    /// All writes succeeded
+    Success,
+    /// Write failed with message
+    Failed(String),
+}
+
+impl SinkOutcome {
+    #[cfg(any(feature = "api", feature = "stdout", feature = "dogstatsd"))]
+    pub(crate) fn from_result<T, E: Display>(result: &Result<T, E>) -> Self {
+        match result {
+            Ok(_) => SinkOutcome::Success,
+            Err(e) => SinkOutcome::Failed(e.to_string()),
+        }
+    }
+
+    /// Whether the sink succeeded
+    pub fn is_success(&self) -> bool {
+        matches!(self, SinkOutcome::Success)
+    }
+}
+
+/// Result of a single flush
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FlushReport {
+    /// Outcome of writing to stdout, [`None`] if disabled
+    pub stdout: Option<SinkOutcome>,
+    /// Outcome of writing to the DataDog API, [`None`] if disabled
+    pub api: Option<SinkOutcome>,
+    /// Outcome of writing to DogStatsD, [`None`] if disabled
+    pub dogstatsd: Option<SinkOutcome>,
+    /// Metric series collected for this flush, split as for the API, whether or not any sink
+    /// delivered them
+    pub series_collected: usize,
+    /// Metric points collected for this flush, whether or not any sink delivered them
+    pub points_collected: usize,
+    /// HTTP requests made to the API
+    pub batches: usize,
+    /// Bytes sent to the API after compression
+    pub compressed_bytes: usize,
+    /// HTTP status codes returned by the API
+    pub statuses: Vec<u16>,
+}
+
+impl FlushReport {
+    /// Whether every enabled sink succeeded
+    pub fn is_success(&self) -> bool {
+        self.outcomes().all(|(_, outcome)| outcome.is_success())
+    }
+
+    fn outcomes(&self) -> impl Iterator<Item = (&'static str, &SinkOutcome)> {
+        [
+            ("stdout", &self.stdout),
+            ("api", &self.api),
+            ("dogstatsd", &self.dogstatsd),
+        ]
+        .into_iter()
+        .filter_map(|(sink, outcome)| outcome.as_ref().map(|o| (sink, o)))
+    }
+}
+
+impl Display for FlushReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let failures = self
+            .outcomes()
+            .filter_map(|(sink, outcome)| match outcome {
+                SinkOutcome::Success => None,
+                SinkOutcome::Failed(e) => Some(format!("{}: {}", sink, e)),
+            })
+            .collect::<Vec<_>>();
+        if failures.is_empty() {
+            write!(f, "collected {} series", self.series_collected)
+        } else {
+            write!(f, "{}", failures.join(", "))
+        }
+    }
+}
+
+/// Status of scheduled flushes, published after every flush
+#[derive(Debug, Clone, Default)]
+pub struct FlushStatus {
+    /// Report from the most recent flush
+    pub last_report: Option<FlushReport>,
+    /// Number of flushes that have failed in a row
+    pub consecutive_failures: u64,
+    /// Time of the most recent successful flush
+    pub last_success: Option<DateTime<Utc>>,
+}
+
+#[cfg(feature = "tokio")]
+impl FlushStatus {
+    pub(crate) fn update(&mut self, report: FlushReport) {
+        if report.is_success() {
+            self.consecutive_failures = 0;
+            self.last_success = Some(Utc::now());
+        } else {
+            self.consecutive_failures += 1;
+        }
+        self.last_report = Some(report);
+    }
+}
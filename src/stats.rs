@@ -0,0 +1,154 @@
+//! Exporter self-telemetry
+//!
+#[cfg(feature = "api")]
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+use metrics::{Key, Level, Metadata};
+use metrics_util::registry::{AtomicStorage, Registry};
+use parking_lot::Mutex;
+
+use crate::filter::MetricFilter;
+
+/// Target of the self-telemetry metrics, e.g. `filter("metrics_datadog_exporter=off")`
+const TARGET: &str = "metrics_datadog_exporter";
+const METADATA: Metadata<'static> = Metadata::new(TARGET, Level::INFO, None);
+
+#[cfg(feature = "api")]
+const SERIES_SENT: &str = "datadog.exporter.series_sent";
+#[cfg(feature = "api")]
+const POINTS_SENT: &str = "datadog.exporter.points_sent";
+#[cfg(feature = "api")]
+const PAYLOAD_BYTES: &str = "datadog.exporter.payload_bytes";
+#[cfg(feature = "api")]
+const COMPRESSED_BYTES: &str = "datadog.exporter.compressed_bytes";
+#[cfg(feature = "api")]
+const BATCHES: &str = "datadog.exporter.batches";
+#[cfg(feature = "api")]
+const FAILURES: &str = "datadog.exporter.failures";
+#[cfg(feature = "api")]
+const DROPPED_POINTS: &str = "datadog.exporter.dropped_points";
+#[cfg(feature = "api")]
+const API_LATENCY: &str = "datadog.exporter.api_latency";
+const FLUSH_DURATION: &str = "datadog.exporter.flush_duration";
+
+/// Snapshot of the exporter's own telemetry since it was created
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExporterStats {
+    /// Number of flushes
+    pub flushes: u64,
+    /// Series accepted by the API
+    pub series_sent: u64,
+    /// Points accepted by the API
+    pub points_sent: u64,
+    /// Payload bytes before compression
+    pub payload_bytes: u64,
+    /// Payload bytes after compression
+    pub compressed_bytes: u64,
+    /// HTTP requests made to the API
+    pub batches: u64,
+    /// HTTP requests that failed
+    pub failures: u64,
+    /// Points in requests that failed
+    pub dropped_points: u64,
+    /// Latency of the most recent API request
+    pub last_api_latency: Option<Duration>,
+    /// Duration of the most recent flush
+    pub last_flush_duration: Option<Duration>,
+}
+
+/// Records exporter telemetry both as metrics and as [`ExporterStats`]
+///
+/// Metrics are only recorded when enabled and not filtered out for the
+/// `metrics_datadog_exporter` target, [`ExporterStats`] always are.
+pub(crate) struct Telemetry {
+    registry: Arc<Registry<Key, AtomicStorage>>,
+    filter: MetricFilter,
+    enabled: bool,
+    stats: Mutex<ExporterStats>,
+}
+
+impl Telemetry {
+    pub(crate) fn new(
+        registry: Arc<Registry<Key, AtomicStorage>>,
+        filter: MetricFilter,
+        enabled: bool,
+    ) -> Self {
+        Telemetry {
+            registry,
+            filter,
+            enabled,
+            stats: Mutex::new(ExporterStats::default()),
+        }
+    }
+
+    fn emit(&self) -> bool {
+        self.enabled && self.filter.enabled(&METADATA)
+    }
+
+    pub(crate) fn stats(&self) -> ExporterStats {
+        self.stats.lock().clone()
+    }
+
+    #[cfg(feature = "api")]
+    pub(crate) fn record_payload(&self, payload_bytes: usize, compressed_bytes: usize) {
+        let mut stats = self.stats.lock();
+        stats.batches += 1;
+        stats.payload_bytes += payload_bytes as u64;
+        stats.compressed_bytes += compressed_bytes as u64;
+        self.increment(BATCHES, 1);
+        self.increment(PAYLOAD_BYTES, payload_bytes as u64);
+        self.increment(COMPRESSED_BYTES, compressed_bytes as u64);
+    }
+
+    #[cfg(feature = "api")]
+    pub(crate) fn record_response(
+        &self,
+        latency: Duration,
+        success: bool,
+        series: usize,
+        points: usize,
+    ) {
+        let mut stats = self.stats.lock();
+        stats.last_api_latency = Some(latency);
+        self.record(API_LATENCY, latency.as_secs_f64());
+        if success {
+            stats.series_sent += series as u64;
+            stats.points_sent += points as u64;
+            self.increment(SERIES_SENT, series as u64);
+            self.increment(POINTS_SENT, points as u64);
+        } else {
+            stats.failures += 1;
+            stats.dropped_points += points as u64;
+            self.increment(FAILURES, 1);
+            self.increment(DROPPED_POINTS, points as u64);
+        }
+    }
+
+    pub(crate) fn record_flush(&self, duration: Duration) {
+        let mut stats = self.stats.lock();
+        stats.flushes += 1;
+        stats.last_flush_duration = Some(duration);
+        self.record(FLUSH_DURATION, duration.as_secs_f64());
+    }
+
+    #[cfg(feature = "api")]
+    fn increment(&self, name: &'static str, value: u64) {
+        if !self.emit() {
+            return;
+        }
+        self.registry
+            .get_or_create_counter(&Key::from_static_name(name), |c| {
+                c.fetch_add(value, Ordering::AcqRel);
+            });
+    }
+
+    fn record(&self, name: &'static str, value: f64) {
+        if !self.emit() {
+            return;
+        }
+        self.registry
+            .get_or_create_histogram(&Key::from_static_name(name), |h| h.push(value));
+    }
+}
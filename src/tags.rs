@@ -0,0 +1,126 @@
+//! Tags added to every metric
+//!
+use metrics::{Key, Label};
+
+/// Computes tags at every flush, e.g. the current deployment color or pod phase
+///
+/// Implemented for closures returning `Vec<(String, String)>`.
+pub trait TagProvider: Send + Sync {
+    /// Tags to add to every metric of this flush
+    fn tags(&self) -> Vec<(String, String)>;
+}
+
+impl<F> TagProvider for F
+where
+    F: Fn() -> Vec<(String, String)> + Send + Sync,
+{
+    fn tags(&self) -> Vec<(String, String)> {
+        self()
+    }
+}
+
+/// Which tag is sent when a metric label, or a service check or event tag, and a global or
+/// dynamic tag share a key
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TagMergePolicy {
+    /// Send the metric label, service check or event tag
+    #[default]
+    MetricWins,
+    /// Send the global or dynamic tag
+    GlobalWins,
+    /// Send both
+    KeepBoth,
+}
+
+/// Global and dynamic tags of a flush
+///
+/// Dynamic tags replace global tags with the same key.
+pub(crate) struct FlushTags {
+    tags: Vec<Label>,
+    policy: TagMergePolicy,
+}
+
+impl FlushTags {
+    pub(crate) fn new(
+        global: &[Label],
+        providers: &[Box<dyn TagProvider>],
+        policy: TagMergePolicy,
+    ) -> Self {
+        let dynamic = providers
+            .iter()
+            .flat_map(|provider| provider.tags())
+            .map(|(key, value)| Label::new(key, value))
+            .collect::<Vec<_>>();
+        let mut tags = global
+            .iter()
+            .filter(|g| !dynamic.iter().any(|d| d.key() == g.key()))
+            .cloned()
+            .collect::<Vec<_>>();
+        tags.extend(dynamic);
+        FlushTags { tags, policy }
+    }
+
+    /// Tags of a metric merged according to the policy, sorted and de-duplicated
+    pub(crate) fn metric_tags(&self, key: &Key) -> Vec<String> {
+        self.merge(
+            key.labels()
+                .map(|l| (l.key(), format!("{}:{}", l.key(), l.value())))
+                .collect(),
+        )
+    }
+
+    /// Tags of a service check or event merged like [`FlushTags::metric_tags`]
+    ///
+    /// The key of a `key:value` tag is the part before the first `:`.
+    pub(crate) fn merge_tags(&self, tags: &[String]) -> Vec<String> {
+        self.merge(
+            tags.iter()
+                .map(|t| (t.split_once(':').map_or(t.as_str(), |(k, _)| k), t.clone()))
+                .collect(),
+        )
+    }
+
+    fn merge(&self, own: Vec<(&str, String)>) -> Vec<String> {
+        let global = self
+            .tags
+            .iter()
+            .map(|l| (l.key(), format!("{}:{}", l.key(), l.value())));
+        let has_own = |key: &str| own.iter().any(|(k, _)| *k == key);
+        let has_global = |key: &str| self.tags.iter().any(|t| t.key() == key);
+        let mut tags: Vec<String> = match self.policy {
+            TagMergePolicy::MetricWins => global
+                .filter(|(k, _)| !has_own(k))
+                .map(|(_, t)| t)
+                .chain(own.iter().map(|(_, t)| t.clone()))
+                .collect(),
+            TagMergePolicy::GlobalWins => global
+                .map(|(_, t)| t)
+                .chain(
+                    own.iter()
+                        .filter(|(k, _)| !has_global(k))
+                        .map(|(_, t)| t.clone()),
+                )
+                .collect(),
+            TagMergePolicy::KeepBoth => global
+                .map(|(_, t)| t)
+                .chain(own.iter().map(|(_, t)| t.clone()))
+                .collect(),
+        };
+        tags.sort();
+        tags.dedup();
+        tags
+    }
+}
+
+/// Key with labels sorted, so keys differing only in label order are the same series
+pub(crate) fn normalize_key(key: &Key) -> Option<Key> {
+    if key
+        .labels()
+        .is_sorted_by(|a, b| (a.key(), a.value()) <= (b.key(), b.value()))
+    {
+        return None;
+    }
+    let mut labels = key.labels().cloned().collect::<Vec<_>>();
+    labels.sort_by(|a, b| (a.key(), a.value()).cmp(&(b.key(), b.value())));
+    Some(Key::from_parts(key.name().to_string(), labels))
+}
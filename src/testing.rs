@@ -0,0 +1,222 @@
+//! In-memory capture sink and helpers for testing code that records metrics
+//!
+//! ```ignore
+//! let test = TestRecorder::new();
+//! let _guard = test.install();
+//! counter!("requests", "route" => "/").increment(1);
+//! test.flush().await?;
+//! test.capture().assert_counter("requests", &[("route", "/")], 1);
+//! ```
+
+use std::sync::Arc;
+
+use metrics::LocalRecorderGuard;
+use parking_lot::Mutex;
+
+use crate::data::{DataDogMetric, DataDogMetricType, DataDogMetricValue};
+use crate::{DataDogBuilder, DataDogExporter, DataDogRecorder, FlushReport, Result};
+
+/// Records every flushed [`DataDogMetric`], see [`DataDogBuilder::capture`]
+#[derive(Clone, Default)]
+pub struct Capture {
+    metrics: Arc<Mutex<Vec<DataDogMetric>>>,
+}
+
+impl Capture {
+    /// Create an empty capture
+    pub fn new() -> Self {
+        Capture::default()
+    }
+
+    pub(crate) fn record(&self, metrics: &[DataDogMetric]) {
+        self.metrics.lock().extend_from_slice(metrics);
+    }
+
+    /// Metrics flushed so far
+    pub fn metrics(&self) -> Vec<DataDogMetric> {
+        self.metrics.lock().clone()
+    }
+
+    /// Forget the metrics flushed so far
+    pub fn clear(&self) {
+        self.metrics.lock().clear();
+    }
+
+    /// Sum of counter `name` with at least `tags` across flushes, `None` if never flushed
+    pub fn counter_value(&self, name: &str, tags: &[(&str, &str)]) -> Option<u64> {
+        self.points(name, DataDogMetricType::Count, tags)
+            .map(|points| {
+                points
+                    .iter()
+                    .map(|point| match point {
+                        DataDogMetricValue::Unsigned(u) => *u,
+                        DataDogMetricValue::Float(f) => *f as u64,
+                    })
+                    .sum()
+            })
+    }
+
+    /// Last value of gauge `name` with at least `tags`, `None` if never flushed
+    pub fn gauge_value(&self, name: &str, tags: &[(&str, &str)]) -> Option<f64> {
+        self.points(name, DataDogMetricType::Gauge, tags)
+            .and_then(|points| points.last().cloned())
+            .map(|point| match point {
+                DataDogMetricValue::Float(f) => f,
+                DataDogMetricValue::Unsigned(u) => u as f64,
+            })
+    }
+
+    /// Number of values recorded in histogram `name` with at least `tags` across flushes
+    pub fn histogram_count(&self, name: &str, tags: &[(&str, &str)]) -> usize {
+        self.points(name, DataDogMetricType::Histogram, tags)
+            .map_or(0, |points| points.len())
+    }
+
+    /// Whether any flushed metric `name` has tag `key:value`
+    pub fn has_tag(&self, name: &str, key: &str, value: &str) -> bool {
+        let tag = format!("{}:{}", key, value);
+        self.metrics
+            .lock()
+            .iter()
+            .any(|m| m.metric == name && m.tags.contains(&tag))
+    }
+
+    /// Panics unless counter `name` with at least `tags` sums to `expected`
+    #[track_caller]
+    pub fn assert_counter(&self, name: &str, tags: &[(&str, &str)], expected: u64) {
+        let actual = self.counter_value(name, tags);
+        assert_eq!(
+            actual,
+            Some(expected),
+            "counter `{}` with tags {:?}, flushed metrics: {:?}",
+            name,
+            tags,
+            self.names()
+        );
+    }
+
+    /// Panics unless histogram `name` with at least `tags` recorded `expected` values
+    #[track_caller]
+    pub fn assert_histogram_count(&self, name: &str, tags: &[(&str, &str)], expected: usize) {
+        let actual = self.histogram_count(name, tags);
+        assert_eq!(
+            actual,
+            expected,
+            "histogram `{}` with tags {:?}, flushed metrics: {:?}",
+            name,
+            tags,
+            self.names()
+        );
+    }
+
+    /// Panics unless a flushed metric `name` has tag `key:value`
+    #[track_caller]
+    pub fn assert_tag(&self, name: &str, key: &str, value: &str) {
+        assert!(
+            self.has_tag(name, key, value),
+            "no metric `{}` with tag `{}:{}`, flushed metrics: {:?}",
+            name,
+            key,
+            value,
+            self.names()
+        );
+    }
+
+    fn points(
+        &self,
+        name: &str,
+        metric_type: DataDogMetricType,
+        tags: &[(&str, &str)],
+    ) -> Option<Vec<DataDogMetricValue>> {
+        let tags = tags
+            .iter()
+            .map(|(k, v)| format!("{}:{}", k, v))
+            .collect::<Vec<_>>();
+        let metrics = self.metrics.lock();
+        let matched = metrics
+            .iter()
+            .filter(|m| m.metric == name && m.metric_type == metric_type)
+            .filter(|m| tags.iter().all(|t| m.tags.contains(t)))
+            .collect::<Vec<_>>();
+        (!matched.is_empty()).then(|| {
+            matched
+                .iter()
+                .flat_map(|m| m.points.iter().map(|(_, value)| value.clone()))
+                .collect()
+        })
+    }
+
+    fn names(&self) -> Vec<String> {
+        let mut names = self
+            .metrics
+            .lock()
+            .iter()
+            .map(|m| m.metric.to_string())
+            .collect::<Vec<_>>();
+        names.sort();
+        names.dedup();
+        names
+    }
+}
+
+/// Recorder and exporter for a single test, capturing flushed metrics instead of writing them
+///
+/// [`TestRecorder::install`] only records metrics from the current thread, so tests running in
+/// parallel don't see each other's metrics. Use a current thread runtime in async tests.
+pub struct TestRecorder {
+    recorder: DataDogRecorder,
+    exporter: DataDogExporter,
+    capture: Capture,
+}
+
+impl TestRecorder {
+    /// Create a recorder that only writes to its [`Capture`]
+    pub fn new() -> Self {
+        let builder = DataDogBuilder::default();
+        #[cfg(feature = "stdout")]
+        let builder = builder.write_to_stdout(false);
+        Self::from_builder(builder).expect("default configuration is valid")
+    }
+
+    /// Create a recorder from `builder`, capturing in addition to its sinks
+    pub fn from_builder(builder: DataDogBuilder) -> Result<Self> {
+        let capture = Capture::new();
+        let (recorder, exporter) = builder.capture(capture.clone()).build_recorder()?;
+        Ok(TestRecorder {
+            recorder,
+            exporter,
+            capture,
+        })
+    }
+
+    /// Record metrics from the current thread until the guard is dropped
+    pub fn install(&self) -> LocalRecorderGuard<'_> {
+        metrics::set_default_local_recorder(&self.recorder)
+    }
+
+    /// Flush to the capture and the configured sinks
+    pub async fn flush(&self) -> Result<FlushReport> {
+        self.exporter.flush().await
+    }
+
+    /// Recorder, e.g. for [`metrics::with_local_recorder`]
+    pub fn recorder(&self) -> &DataDogRecorder {
+        &self.recorder
+    }
+
+    /// Exporter, e.g. to queue service checks
+    pub fn exporter(&self) -> &DataDogExporter {
+        &self.exporter
+    }
+
+    /// Metrics flushed so far
+    pub fn capture(&self) -> &Capture {
+        &self.capture
+    }
+}
+
+impl Default for TestRecorder {
+    fn default() -> Self {
+        TestRecorder::new()
+    }
+}
@@ -2,12 +2,21 @@ use anyhow::Result;
 use assert_json_diff::{assert_json_matches_no_panic, CompareMode, Config};
 use httpmock::Method::POST;
 use httpmock::MockServer;
-use metrics::histogram;
+use metrics::{histogram, Key, Label, Level, Metadata, Recorder};
 use metrics_datadog_exporter::data::DataDogSeries;
-use metrics_datadog_exporter::DataDogBuilder;
+use metrics_datadog_exporter::reqwest::header::{HeaderName, HeaderValue};
+use metrics_datadog_exporter::{
+    ApiKey, DataDogBuilder, DataDogEvent, DataDogEventAlertType, DataDogServiceCheckStatus, Error,
+    SinkOutcome,
+};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::io::Read;
+use std::sync::{Arc, Mutex};
+
+const METADATA: Metadata = Metadata::new(module_path!(), Level::INFO, None);
+// Intake limit on compressed payloads
+const MAX_PAYLOAD_BYTES: usize = 3200000;
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 struct DataDogPost {
@@ -28,7 +37,7 @@ async fn write_to_api_compressed_test() -> Result<()> {
         .install()?;
 
     for i in 0..10 {
-        histogram!("metric", i as f64);
+        histogram!("metric").record(i as f64);
     }
     let mock = server.mock(|when, then| {
         when.method(POST).path("/series").matches(|req| {
@@ -73,7 +82,7 @@ async fn write_to_api_uncompressed_test() -> Result<()> {
         .install()?;
 
     for i in 0..10 {
-        histogram!("metric", i as f64);
+        histogram!("metric").record(i as f64);
     }
     let mock = server.mock(|when, then| {
         when.method(POST).path("/series").matches(|req| {
@@ -97,3 +106,487 @@ async fn write_to_api_uncompressed_test() -> Result<()> {
     mock.assert();
     Ok(())
 }
+
+#[tokio::test]
+async fn write_service_check_test() -> Result<()> {
+    let server = MockServer::start();
+
+    let metrics = DataDogBuilder::default()
+        .write_to_stdout(false)
+        .write_to_api(true, Some("DUMMY".to_string()))
+        .api_host(server.base_url())
+        .tags(vec![("env".to_string(), "test".to_string())])
+        .gzip(false)
+        .build()?;
+
+    metrics.service_check(
+        "app.can_connect",
+        DataDogServiceCheckStatus::Critical,
+        vec![("db".to_string(), "primary".to_string())],
+        Some("connection refused".to_string()),
+    );
+    let mock = server.mock(|when, then| {
+        when.method(POST)
+            .path("/check_run")
+            .header("DD-API-KEY", "DUMMY")
+            .matches(|req| {
+                let body = req.body.clone().unwrap();
+                let expected = json!([{
+                    "check": "app.can_connect",
+                    "status": 2,
+                    "message": "connection refused",
+                    "tags": ["db:primary", "env:test"]
+                }]);
+                let j: Value = serde_json::from_slice(body.as_slice()).expect("");
+                assert_json_matches_no_panic(&j, &expected, Config::new(CompareMode::Inclusive))
+                    .is_ok()
+            });
+        then.status(202);
+    });
+
+    metrics.flush().await?;
+    mock.assert_hits(1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn write_event_test() -> Result<()> {
+    let server = MockServer::start();
+
+    let metrics = DataDogBuilder::default()
+        .write_to_stdout(false)
+        .write_to_api(true, Some("DUMMY".to_string()))
+        .api_host(server.base_url())
+        .tags(vec![("env".to_string(), "test".to_string())])
+        .build()?;
+
+    for i in 0..2 {
+        metrics.event(
+            DataDogEvent::new(format!("Deploy {}", i), "Deployed".to_string())
+                .alert_type(DataDogEventAlertType::Success)
+                .aggregation_key("deploy".to_string())
+                .tags(vec![("version".to_string(), i.to_string())]),
+        );
+    }
+    let mock = server.mock(|when, then| {
+        when.method(POST)
+            .path("/events")
+            .header("Content-Encoding", "gzip")
+            .matches(|req| {
+                let body = req.body.clone().unwrap();
+                let mut gz = flate2::read::GzDecoder::new(body.as_slice());
+                let mut buffer = Vec::new();
+                if gz.read_to_end(&mut buffer).is_err() {
+                    return false;
+                }
+                let expected = json!({
+                    "text": "Deployed",
+                    "alert_type": "success",
+                    "aggregation_key": "deploy",
+                    "tags": ["env:test"]
+                });
+                let j: Value = serde_json::from_slice(buffer.as_slice()).expect("");
+                assert_json_matches_no_panic(&j, &expected, Config::new(CompareMode::Inclusive))
+                    .is_ok()
+            });
+        then.status(202);
+    });
+
+    metrics.flush().await?;
+    mock.assert_hits(2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn exporter_stats_test() -> Result<()> {
+    let server = MockServer::start();
+
+    let metrics = DataDogBuilder::default()
+        .write_to_stdout(false)
+        .write_to_api(true, Some("DUMMY".to_string()))
+        .api_host(server.base_url())
+        .build()?;
+
+    let histogram = metrics
+        .recorder
+        .register_histogram(&Key::from_name("metric"), &METADATA);
+    for i in 0..4 {
+        histogram.record(i as f64);
+    }
+    let mock = server.mock(|when, then| {
+        when.method(POST).path("/series");
+        then.status(202);
+    });
+
+    let report = metrics.flush().await?;
+    assert_eq!(report.series_collected, 2);
+    assert_eq!(report.points_collected, 4);
+    let stats = metrics.stats();
+    assert_eq!(stats.flushes, 1);
+    assert_eq!(stats.batches, 1);
+    assert_eq!(stats.series_sent, 2);
+    assert_eq!(stats.points_sent, 4);
+    assert_eq!(stats.failures, 0);
+    assert!(stats.compressed_bytes < stats.payload_bytes);
+
+    // telemetry from the first flush is exported on the next one
+    let collected = metrics.handle.collect();
+    assert!(collected
+        .iter()
+        .any(|m| m.metric == "datadog.exporter.series_sent"));
+    mock.assert_hits(1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn flush_report_test() -> Result<()> {
+    let server = MockServer::start();
+
+    let metrics = DataDogBuilder::default()
+        .write_to_stdout(false)
+        .write_to_api(true, Some("DUMMY".to_string()))
+        .api_host(server.base_url())
+        .build()?;
+
+    let counter = metrics
+        .recorder
+        .register_counter(&Key::from_name("metric"), &METADATA);
+    counter.increment(1);
+    let mut mock = server.mock(|when, then| {
+        when.method(POST).path("/series");
+        then.status(500);
+    });
+
+    let report = match metrics.flush().await {
+        Err(Error::Flush(report)) => report,
+        other => panic!("Expected flush failure, got {:?}", other),
+    };
+    assert!(report.stdout.is_none());
+    assert!(matches!(report.api, Some(SinkOutcome::Failed(_))));
+    assert_eq!(report.series_collected, 1);
+    assert_eq!(report.batches, 1);
+    assert_eq!(report.statuses, vec![500]);
+    mock.delete();
+
+    server.mock(|when, then| {
+        when.method(POST).path("/series");
+        then.status(202);
+    });
+    counter.increment(1);
+    let report = metrics.flush().await?;
+    assert_eq!(report.api, Some(SinkOutcome::Success));
+    assert_eq!(report.statuses, vec![202]);
+    Ok(())
+}
+
+#[cfg(feature = "deflate")]
+#[tokio::test]
+async fn write_to_api_deflate_test() -> Result<()> {
+    use metrics_datadog_exporter::Compression;
+
+    let server = MockServer::start();
+
+    let metrics = DataDogBuilder::default()
+        .write_to_stdout(false)
+        .write_to_api(true, Some("DUMMY".to_string()))
+        .api_host(server.base_url())
+        .compression(Compression::Deflate(9))
+        .build()?;
+
+    let histogram = metrics
+        .recorder
+        .register_histogram(&Key::from_name("metric"), &METADATA);
+    histogram.record(1.0);
+    let mock = server.mock(|when, then| {
+        when.method(POST)
+            .path("/series")
+            .header("Content-Encoding", "deflate")
+            .matches(|req| {
+                let body = req.body.clone().unwrap();
+                let mut zlib = flate2::read::ZlibDecoder::new(body.as_slice());
+                let mut buffer = Vec::new();
+                zlib.read_to_end(&mut buffer).is_ok()
+                    && serde_json::from_slice::<DataDogPost>(&buffer).is_ok()
+            });
+        then.status(202);
+    });
+
+    metrics.flush().await?;
+    mock.assert_hits(1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn write_to_api_split_payload_test() -> Result<()> {
+    let server = MockServer::start();
+
+    let metrics = DataDogBuilder::default()
+        .write_to_stdout(false)
+        .write_to_api(true, Some("DUMMY".to_string()))
+        .api_host(server.base_url())
+        .gzip(false)
+        .build()?;
+
+    let count = 50000;
+    for i in 0..count {
+        let key = Key::from_parts(format!("metric.{}", i), vec![]);
+        metrics
+            .recorder
+            .register_counter(&key, &METADATA)
+            .increment(1);
+    }
+    let mock = server.mock(|when, then| {
+        when.method(POST).path("/series").matches(|req| {
+            let body = req.body.clone().unwrap();
+            body.len() < MAX_PAYLOAD_BYTES && serde_json::from_slice::<DataDogPost>(&body).is_ok()
+        });
+        then.status(202);
+    });
+
+    let report = metrics.flush().await?;
+    assert!(report.batches > 1);
+    // payloads are filled up to the limit
+    assert_eq!(
+        report.batches,
+        report.compressed_bytes.div_ceil(MAX_PAYLOAD_BYTES)
+    );
+    mock.assert_hits(report.batches);
+    assert_eq!(metrics.stats().series_sent, count);
+    Ok(())
+}
+
+#[tokio::test]
+async fn write_to_api_split_gzip_payload_test() -> Result<()> {
+    let server = MockServer::start();
+
+    let metrics = DataDogBuilder::default()
+        .write_to_stdout(false)
+        .write_to_api(true, Some("DUMMY".to_string()))
+        .api_host(server.base_url())
+        .build()?;
+
+    // high entropy names and values so the payload doesn't compress well
+    let mut state = 0x2545f4914f6cdd1d_u64;
+    let mut random = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    let count = 150000;
+    for _ in 0..count {
+        let key = Key::from_parts(
+            format!("metric.{:016x}", random()),
+            vec![Label::new("id", format!("{:016x}", random()))],
+        );
+        metrics
+            .recorder
+            .register_gauge(&key, &METADATA)
+            .set(f64::from_bits(random() >> 2));
+    }
+    let mock = server.mock(|when, then| {
+        when.method(POST).path("/series").matches(|req| {
+            let body = req.body.clone().unwrap();
+            let mut buffer = Vec::new();
+            body.len() < MAX_PAYLOAD_BYTES
+                && flate2::read::GzDecoder::new(body.as_slice())
+                    .read_to_end(&mut buffer)
+                    .is_ok()
+                && serde_json::from_slice::<DataDogPost>(&buffer).is_ok()
+        });
+        then.status(202);
+    });
+
+    let report = metrics.flush().await?;
+    assert!(report.batches > 1);
+    // payloads are filled up to the limit
+    assert_eq!(
+        report.batches,
+        report.compressed_bytes.div_ceil(MAX_PAYLOAD_BYTES)
+    );
+    mock.assert_hits(report.batches);
+    assert_eq!(metrics.stats().series_sent, count);
+    Ok(())
+}
+
+#[tokio::test]
+async fn write_to_api_partial_failure_test() -> Result<()> {
+    let server = MockServer::start();
+
+    let metrics = DataDogBuilder::default()
+        .write_to_stdout(false)
+        .write_to_api(true, Some("DUMMY".to_string()))
+        .api_host(server.base_url())
+        .max_in_flight(1)
+        .gzip(false)
+        .build()?;
+
+    for title in ["ok", "fail", "ok"] {
+        metrics.event(DataDogEvent::new(title.to_string(), String::new()));
+    }
+    let failing = server.mock(|when, then| {
+        when.method(POST)
+            .path("/events")
+            .json_body_partial(r#"{"title": "fail"}"#);
+        then.status(500);
+    });
+    let ok = server.mock(|when, then| {
+        when.method(POST)
+            .path("/events")
+            .json_body_partial(r#"{"title": "ok"}"#);
+        then.status(202);
+    });
+
+    match metrics.flush().await {
+        Err(Error::Flush(report)) => {
+            assert_eq!(report.batches, 3);
+            let mut statuses = report.statuses.clone();
+            statuses.sort();
+            assert_eq!(statuses, vec![202, 202, 500]);
+        }
+        other => panic!("Expected flush failure, got {:?}", other),
+    }
+    failing.assert_hits(1);
+    ok.assert_hits(2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn write_to_api_through_proxy_test() -> Result<()> {
+    let proxy = MockServer::start();
+
+    let metrics = DataDogBuilder::default()
+        .write_to_stdout(false)
+        .write_to_api(true, Some("DUMMY".to_string()))
+        .api_host("http://datadog.invalid/api/v1".to_string())
+        .proxy(proxy.base_url())
+        .no_proxy("localhost".to_string())
+        .build()?;
+
+    metrics
+        .recorder
+        .register_counter(&Key::from_name("metric"), &METADATA)
+        .increment(1);
+    let mock = proxy.mock(|when, then| {
+        when.method(POST)
+            .path("/api/v1/series")
+            .header("host", "datadog.invalid");
+        then.status(202);
+    });
+
+    metrics.flush().await?;
+    mock.assert_hits(1);
+    // the final flush on drop uses a blocking client with the same proxy
+    drop(metrics);
+    mock.assert_hits(2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn forward_to_agent_test() -> Result<()> {
+    let agent = MockServer::start();
+
+    let metrics = DataDogBuilder::default()
+        .write_to_stdout(false)
+        .forward_to(agent.base_url(), None)
+        .header(
+            HeaderName::from_static("x-forwarded-by"),
+            HeaderValue::from_static("exporter"),
+        )
+        .build()?;
+
+    metrics
+        .recorder
+        .register_counter(&Key::from_name("metric"), &METADATA)
+        .increment(1);
+    let mock = agent.mock(|when, then| {
+        when.method(POST)
+            .path("/api/v1/series")
+            .header("x-forwarded-by", "exporter")
+            .matches(|request| {
+                !request
+                    .headers
+                    .iter()
+                    .flatten()
+                    .any(|(name, _)| name.eq_ignore_ascii_case("dd-api-key"))
+            });
+        then.status(202);
+    });
+
+    metrics.flush().await?;
+    mock.assert_hits(1);
+    Ok(())
+}
+
+#[test]
+fn api_key_debug_is_redacted_test() {
+    let api_key = ApiKey::new("SECRET");
+    assert!(!format!("{:?}", api_key).contains("SECRET"));
+}
+
+#[tokio::test]
+async fn rotate_api_key_test() -> Result<()> {
+    let server = MockServer::start();
+
+    let metrics = DataDogBuilder::default()
+        .write_to_stdout(false)
+        .write_to_api(true, Some("OLD".to_string()))
+        .api_host(server.url("/api/v1"))
+        .build()?;
+
+    let old = server.mock(|when, then| {
+        when.method(POST).header("DD-API-KEY", "OLD");
+        then.status(202);
+    });
+    let new = server.mock(|when, then| {
+        when.method(POST).header("DD-API-KEY", "NEW");
+        then.status(202);
+    });
+
+    metrics
+        .recorder
+        .register_counter(&Key::from_name("metric"), &METADATA)
+        .increment(1);
+    metrics.flush().await?;
+    metrics.set_api_key(Some(ApiKey::new("NEW")));
+    metrics.flush().await?;
+
+    old.assert_hits(1);
+    assert!(new.hits() >= 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn api_key_provider_test() -> Result<()> {
+    let server = MockServer::start();
+    let key = Arc::new(Mutex::new("FIRST".to_string()));
+
+    let provider_key = key.clone();
+    let metrics = DataDogBuilder::default()
+        .write_to_stdout(false)
+        .write_to_api(true, None)
+        .api_key_provider(move || Some(ApiKey::new(provider_key.lock().unwrap().clone())))
+        .api_host(server.url("/api/v1"))
+        .build()?;
+
+    let first = server.mock(|when, then| {
+        when.method(POST).header("DD-API-KEY", "FIRST");
+        then.status(202);
+    });
+    let second = server.mock(|when, then| {
+        when.method(POST).header("DD-API-KEY", "SECOND");
+        then.status(202);
+    });
+
+    metrics
+        .recorder
+        .register_counter(&Key::from_name("metric"), &METADATA)
+        .increment(1);
+    metrics.flush().await?;
+    *key.lock().unwrap() = "SECOND".to_string();
+    metrics.flush().await?;
+
+    first.assert_hits(1);
+    assert!(second.hits() >= 1);
+    Ok(())
+}
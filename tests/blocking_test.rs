@@ -0,0 +1,62 @@
+use anyhow::Result;
+use httpmock::Method::POST;
+use httpmock::MockServer;
+use metrics::{Key, Level, Metadata, Recorder};
+use metrics_datadog_exporter::{DataDogBuilder, Error, SinkOutcome};
+use std::thread::sleep;
+use std::time::Duration;
+
+const METADATA: Metadata = Metadata::new(module_path!(), Level::INFO, None);
+
+#[test]
+fn flush_blocking_test() -> Result<()> {
+    let server = MockServer::start();
+
+    let metrics = DataDogBuilder::default()
+        .write_to_stdout(false)
+        .write_to_api(true, Some("DUMMY".to_string()))
+        .api_host(server.base_url())
+        .build()?;
+
+    let counter = metrics
+        .recorder
+        .register_counter(&Key::from_name("metric"), &METADATA);
+    counter.increment(1);
+    let mock = server.mock(|when, then| {
+        when.method(POST).path("/series");
+        then.status(202);
+    });
+
+    let report = metrics.flush_blocking()?;
+    assert_eq!(report.api, Some(SinkOutcome::Success));
+    assert_eq!(report.statuses, vec![202]);
+    mock.assert_hits(1);
+
+    let recorder = metrics.recorder;
+    let scheduler = metrics
+        .handle
+        .schedule_blocking(Duration::from_millis(50))?;
+    recorder
+        .register_counter(&Key::from_name("metric"), &METADATA)
+        .increment(1);
+    sleep(Duration::from_millis(200));
+    scheduler.shutdown();
+    // later flushes also carry the exporter's own telemetry
+    let hits = mock.hits();
+    assert!(hits >= 2);
+
+    // the thread was joined, nothing is flushed after shutdown returns
+    sleep(Duration::from_millis(200));
+    assert_eq!(mock.hits(), hits);
+    Ok(())
+}
+
+#[test]
+fn schedule_blocking_zero_interval_test() -> Result<()> {
+    let metrics = DataDogBuilder::default().write_to_stdout(false).build()?;
+    assert!(matches!(
+        metrics.schedule_blocking(Duration::ZERO),
+        Err(Error::Config(_))
+    ));
+    Ok(())
+}
@@ -1,17 +1,24 @@
 use anyhow::Result;
-use metrics::{counter, gauge, histogram};
+use metrics::{counter, gauge, histogram, with_local_recorder, Key, Level, Metadata, Recorder};
 use metrics_datadog_exporter::{
-    DataDogBuilder, DataDogMetric, DataDogMetricType, DataDogMetricValue,
+    data::DataDogSeries, DataDogBuilder, DataDogMetric, DataDogMetricType, DataDogMetricValue,
+    ManualClock, TagMergePolicy,
 };
+use metrics_util::debugging::DebuggingRecorder;
+use metrics_util::layers::{FanoutBuilder, Layer, PrefixLayer};
 use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const METADATA: Metadata = Metadata::new(module_path!(), Level::INFO, None);
 
 #[test]
 fn collect_test() -> Result<()> {
     let metrics = DataDogBuilder::default().build()?.install()?;
-    counter!("this.counter", 123, "tag2" => "value2");
-    gauge!("this.gauge", 234.0, "tag3" => "value3");
-    histogram!("this.histogram", 345.0, "tag4" => "value5");
-    histogram!("this.histogram", 456.0, "tag4" => "value5");
+    counter!("this.counter", "tag2" => "value2").increment(123);
+    gauge!("this.gauge", "tag3" => "value3").set(234.0);
+    histogram!("this.histogram", "tag4" => "value5").record(345.0);
+    histogram!("this.histogram", "tag4" => "value5").record(456.0);
     let collected = metrics
         .collect()
         .into_iter()
@@ -21,9 +28,327 @@ fn collect_test() -> Result<()> {
     let counter = collected.get("this.counter").unwrap();
     assert_eq!(counter.metric_type, DataDogMetricType::Count);
     assert_eq!(counter.tags, vec!["tag2:value2".to_string()]);
-    assert_eq!(counter.points, vec![DataDogMetricValue::Unsigned(123)]);
+    assert_eq!(counter.points[0].1, DataDogMetricValue::Unsigned(123));
     let histogram = collected.get("this.histogram").unwrap();
     assert_eq!(histogram.metric_type, DataDogMetricType::Histogram);
     assert_eq!(histogram.points.len(), 2);
     Ok(())
 }
+
+#[test]
+fn layered_recorder_test() -> Result<()> {
+    let (recorder, exporter) = DataDogBuilder::default().build_recorder()?;
+    let debugging = DebuggingRecorder::new();
+    let snapshotter = debugging.snapshotter();
+    let fanout = FanoutBuilder::default()
+        .add_recorder(PrefixLayer::new("app").layer(recorder.clone()))
+        .add_recorder(debugging)
+        .build();
+
+    fanout
+        .register_counter(&Key::from_name("requests"), &METADATA)
+        .increment(2);
+    recorder
+        .register_counter(&Key::from_name("direct"), &METADATA)
+        .increment(1);
+
+    let mut collected = exporter
+        .collect()
+        .into_iter()
+        .map(|m| m.metric)
+        .collect::<Vec<_>>();
+    collected.sort();
+    assert_eq!(collected, vec!["app.requests", "direct"]);
+    assert_eq!(snapshotter.snapshot().into_vec().len(), 1);
+    Ok(())
+}
+
+#[test]
+fn target_filter_test() -> Result<()> {
+    let (recorder, exporter) = DataDogBuilder::default()
+        .targets(vec!["app::db".to_string()])
+        .build_recorder()?;
+
+    with_local_recorder(&recorder, || {
+        counter!(target: "app::db", "queries").increment(1);
+        counter!(target: "app::db::pool", "connections").increment(1);
+        counter!(target: "app::dbx", "other").increment(1);
+        counter!(target: "app::http", "requests").increment(1);
+    });
+
+    let mut collected = exporter
+        .collect()
+        .into_iter()
+        .map(|m| m.metric)
+        .collect::<Vec<_>>();
+    collected.sort();
+    assert_eq!(collected, vec!["connections", "queries"]);
+    Ok(())
+}
+
+#[test]
+fn level_and_directive_filter_test() -> Result<()> {
+    let (recorder, exporter) = DataDogBuilder::default()
+        .min_level(Level::INFO)
+        .filter("hyper=off,app::db=debug".to_string())
+        .build_recorder()?;
+
+    with_local_recorder(&recorder, || {
+        counter!(target: "app", level: Level::DEBUG, "app.debug").increment(1);
+        counter!(target: "app", "app.info").increment(1);
+        counter!(target: "app::db", level: Level::DEBUG, "db.debug").increment(1);
+        counter!(target: "app::db", level: Level::TRACE, "db.trace").increment(1);
+        counter!(target: "hyper::client", level: Level::ERROR, "hyper.error").increment(1);
+    });
+
+    let mut collected = exporter
+        .collect()
+        .into_iter()
+        .map(|m| m.metric)
+        .collect::<Vec<_>>();
+    collected.sort();
+    assert_eq!(collected, vec!["app.info", "db.debug"]);
+    Ok(())
+}
+
+#[test]
+fn reload_filter_test() -> Result<()> {
+    let (recorder, exporter) = DataDogBuilder::default().build_recorder()?;
+
+    exporter.filter().reload("app=off,warn")?;
+    with_local_recorder(&recorder, || {
+        counter!(target: "app", level: Level::ERROR, "app.error").increment(1);
+        counter!(target: "other", "other.info").increment(1);
+        counter!(target: "other", level: Level::WARN, "other.warn").increment(1);
+    });
+    let collected = exporter
+        .collect()
+        .into_iter()
+        .map(|m| m.metric)
+        .collect::<Vec<_>>();
+    assert_eq!(collected, vec!["other.warn"]);
+
+    assert!(exporter.filter().reload("app=loud").is_err());
+    Ok(())
+}
+
+#[test]
+fn tag_provider_test() -> Result<()> {
+    let color = Arc::new(Mutex::new("blue"));
+    let provider_color = color.clone();
+    let (recorder, exporter) = DataDogBuilder::default()
+        .tags(vec![
+            ("env".to_string(), "prod".to_string()),
+            ("color".to_string(), "none".to_string()),
+        ])
+        .tag_provider(move || {
+            vec![
+                (
+                    "color".to_string(),
+                    provider_color.lock().unwrap().to_string(),
+                ),
+                ("cohort".to_string(), "a".to_string()),
+            ]
+        })
+        .build_recorder()?;
+
+    with_local_recorder(&recorder, || {
+        counter!("requests").increment(1);
+        counter!("overridden", "cohort" => "b").increment(1);
+    });
+    let mut collected = exporter.collect();
+    collected.sort_by(|a, b| a.metric.cmp(&b.metric));
+    assert_eq!(
+        collected[0].tags,
+        vec!["cohort:b", "color:blue", "env:prod"]
+    );
+    assert_eq!(
+        collected[1].tags,
+        vec!["cohort:a", "color:blue", "env:prod"]
+    );
+
+    *color.lock().unwrap() = "green";
+    with_local_recorder(&recorder, || counter!("requests").increment(1));
+    assert_eq!(
+        exporter.collect()[0].tags,
+        vec!["cohort:a", "color:green", "env:prod"]
+    );
+    Ok(())
+}
+
+fn merged_tags(policy: TagMergePolicy) -> Result<Vec<String>> {
+    let (recorder, exporter) = DataDogBuilder::default()
+        .tags(vec![
+            ("env".to_string(), "prod".to_string()),
+            ("team".to_string(), "core".to_string()),
+        ])
+        .tag_merge_policy(policy)
+        .build_recorder()?;
+    with_local_recorder(&recorder, || {
+        counter!("requests", "env" => "staging", "team" => "core").increment(1)
+    });
+    Ok(exporter.collect().remove(0).tags)
+}
+
+#[test]
+fn tag_merge_policy_test() -> Result<()> {
+    assert_eq!(
+        merged_tags(TagMergePolicy::MetricWins)?,
+        vec!["env:staging", "team:core"]
+    );
+    assert_eq!(
+        merged_tags(TagMergePolicy::GlobalWins)?,
+        vec!["env:prod", "team:core"]
+    );
+    assert_eq!(
+        merged_tags(TagMergePolicy::KeepBoth)?,
+        vec!["env:prod", "env:staging", "team:core"]
+    );
+    Ok(())
+}
+
+#[test]
+fn label_order_is_one_series_test() -> Result<()> {
+    let (recorder, exporter) = DataDogBuilder::default().build_recorder()?;
+    with_local_recorder(&recorder, || {
+        counter!("requests", "a" => "1", "b" => "2").increment(1);
+        counter!("requests", "b" => "2", "a" => "1").increment(2);
+    });
+    let collected = exporter.collect();
+    assert_eq!(collected.len(), 1);
+    assert_eq!(collected[0].tags, vec!["a:1", "b:2"]);
+    assert_eq!(collected[0].points[0].1, DataDogMetricValue::Unsigned(3));
+    Ok(())
+}
+
+#[test]
+fn manual_clock_test() -> Result<()> {
+    let clock = ManualClock::from_timestamp(1_700_000_003);
+    let (recorder, exporter) = DataDogBuilder::default()
+        .clock(clock.clone())
+        .build_recorder()?;
+    with_local_recorder(&recorder, || {
+        counter!("requests").increment(1);
+        gauge!("queue").set(1.0);
+        histogram!("latency").record(1.0);
+    });
+    let timestamps = exporter
+        .collect()
+        .into_iter()
+        .map(|m| m.points[0].0)
+        .collect::<Vec<_>>();
+    assert_eq!(timestamps, vec![1_700_000_003; 3]);
+
+    clock.advance(Duration::from_secs(5));
+    with_local_recorder(&recorder, || counter!("requests").increment(1));
+    assert_eq!(exporter.collect()[0].points[0].0, 1_700_000_008);
+    Ok(())
+}
+
+#[test]
+fn align_timestamps_test() -> Result<()> {
+    let (recorder, exporter) = DataDogBuilder::default()
+        .clock(ManualClock::from_timestamp(1_700_000_007))
+        .align_timestamps(Duration::from_secs(10))
+        .build_recorder()?;
+    with_local_recorder(&recorder, || counter!("requests").increment(1));
+    assert_eq!(exporter.collect()[0].points[0].0, 1_700_000_000);
+    Ok(())
+}
+
+#[test]
+fn series_grouping_test() {
+    let metric = |metric: &str, timestamp: i64, value: u64| DataDogMetric {
+        metric: metric.to_string(),
+        metric_type: DataDogMetricType::Count,
+        points: vec![(timestamp, DataDogMetricValue::Unsigned(value))],
+        tags: vec!["a:1".to_string()],
+    };
+    let series = DataDogSeries::group(
+        vec![
+            metric("requests", 1_700_000_020, 3),
+            metric("errors", 1_700_000_010, 1),
+            metric("requests", 1_700_000_000, 1),
+            metric("requests", 1_700_000_010, 2),
+        ],
+        None,
+    );
+    assert_eq!(series.len(), 2);
+    assert_eq!(series[0].metric, "requests");
+    assert_eq!(
+        series[0].points,
+        vec![
+            (1_700_000_000, DataDogMetricValue::Unsigned(1)),
+            (1_700_000_010, DataDogMetricValue::Unsigned(2)),
+            (1_700_000_020, DataDogMetricValue::Unsigned(3)),
+        ]
+    );
+    assert_eq!(series[0].interval, Some(10));
+    assert_eq!(series[1].metric, "errors");
+    assert_eq!(series[1].interval, None);
+}
+
+#[test]
+fn series_duplicate_timestamps_test() {
+    let metric = |metric_type: DataDogMetricType, timestamp: i64, value: u64| DataDogMetric {
+        metric: "requests".to_string(),
+        metric_type,
+        points: vec![(timestamp, DataDogMetricValue::Unsigned(value))],
+        tags: vec![],
+    };
+    let counts = DataDogSeries::group(
+        vec![
+            metric(DataDogMetricType::Count, 1_700_000_010, 2),
+            metric(DataDogMetricType::Count, 1_700_000_000, 1),
+            metric(DataDogMetricType::Count, 1_700_000_010, 3),
+        ],
+        None,
+    );
+    assert_eq!(
+        counts[0].points,
+        vec![
+            (1_700_000_000, DataDogMetricValue::Unsigned(1)),
+            (1_700_000_010, DataDogMetricValue::Unsigned(5)),
+        ]
+    );
+    assert_eq!(counts[0].interval, Some(10));
+
+    let gauges = DataDogSeries::group(
+        vec![
+            metric(DataDogMetricType::Gauge, 1_700_000_000, 4),
+            metric(DataDogMetricType::Gauge, 1_700_000_000, 7),
+        ],
+        None,
+    );
+    assert_eq!(
+        gauges[0].points,
+        vec![(1_700_000_000, DataDogMetricValue::Unsigned(7))]
+    );
+
+    let histograms = DataDogSeries::group(
+        vec![
+            metric(DataDogMetricType::Histogram, 1_700_000_000, 4),
+            metric(DataDogMetricType::Histogram, 1_700_000_000, 7),
+        ],
+        None,
+    );
+    assert_eq!(histograms[0].points.len(), 2);
+}
+
+#[test]
+fn series_flush_interval_test() {
+    let count = DataDogMetric {
+        metric: "requests".to_string(),
+        metric_type: DataDogMetricType::Count,
+        points: vec![(1_700_000_000, DataDogMetricValue::Unsigned(1))],
+        tags: vec![],
+    };
+    assert_eq!(
+        DataDogSeries::group(vec![count.clone()], None)[0].interval,
+        None
+    );
+    assert_eq!(
+        DataDogSeries::group(vec![count.clone(), count], Some(10))[0].interval,
+        Some(10)
+    );
+}
@@ -0,0 +1,24 @@
+use anyhow::Result;
+use metrics_021::{Key, Label, Recorder};
+use metrics_datadog_exporter::{DataDogBuilder, DataDogMetricType, DataDogMetricValue};
+
+#[test]
+fn metrics_021_recorder_test() -> Result<()> {
+    let (recorder, exporter) = DataDogBuilder::default().build_recorder()?;
+
+    let key = Key::from_parts("legacy.counter", vec![Label::new("tag", "value")]);
+    recorder.register_counter(&key).increment(3);
+    recorder
+        .register_histogram(&Key::from_name("legacy.histogram"))
+        .record(1.5);
+
+    let mut collected = exporter.collect();
+    collected.sort_by(|a, b| a.metric.cmp(&b.metric));
+    assert_eq!(collected.len(), 2);
+    assert_eq!(collected[0].metric, "legacy.counter");
+    assert_eq!(collected[0].metric_type, DataDogMetricType::Count);
+    assert_eq!(collected[0].tags, vec!["tag:value".to_string()]);
+    assert_eq!(collected[0].points[0].1, DataDogMetricValue::Unsigned(3));
+    assert_eq!(collected[1].metric_type, DataDogMetricType::Histogram);
+    Ok(())
+}
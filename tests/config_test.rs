@@ -0,0 +1,89 @@
+use std::time::Duration;
+
+use metrics_datadog_exporter::reqwest;
+use metrics_datadog_exporter::{Compression, DataDogBuilder, Error};
+
+fn config_problems(builder: DataDogBuilder) -> Vec<String> {
+    match builder.build() {
+        Err(Error::Config(problems)) => problems,
+        Err(e) => panic!("unexpected error: {}", e),
+        Ok(_) => panic!("expected a configuration error"),
+    }
+}
+
+#[test]
+fn missing_api_key_test() {
+    let problems = config_problems(DataDogBuilder::default().write_to_api(true, None));
+    assert_eq!(
+        problems,
+        vec!["an API key is required to write to the DataDog API"]
+    );
+}
+
+#[test]
+fn forward_without_api_key_test() {
+    assert!(DataDogBuilder::default()
+        .forward_to("http://localhost:8080".to_string(), None)
+        .build()
+        .is_ok());
+}
+
+#[test]
+fn reports_every_problem_test() {
+    let problems = config_problems(
+        DataDogBuilder::default()
+            .write_to_api(true, Some("DUMMY".to_string()))
+            .api_host("not a url".to_string())
+            .max_in_flight(0)
+            .compression(Compression::Gzip(12))
+            .client_timeout(Duration::ZERO)
+            .client(reqwest::Client::new()),
+    );
+    assert_eq!(problems.len(), 5, "{:?}", problems);
+}
+
+#[test]
+fn api_disabled_is_not_validated_test() {
+    assert!(DataDogBuilder::default()
+        .write_to_api(false, None)
+        .api_host("not a url".to_string())
+        .build()
+        .is_ok());
+}
+
+#[test]
+fn invalid_filter_test() {
+    let problems = config_problems(DataDogBuilder::default().filter("app=loud".to_string()));
+    assert_eq!(problems.len(), 1);
+    assert!(problems[0].starts_with("invalid filter"), "{:?}", problems);
+}
+
+#[test]
+fn align_timestamps_interval_test() {
+    let problems = config_problems(DataDogBuilder::default().align_timestamps(Duration::ZERO));
+    assert_eq!(problems.len(), 1, "{:?}", problems);
+    let problems =
+        config_problems(DataDogBuilder::default().align_timestamps(Duration::from_millis(500)));
+    assert_eq!(problems.len(), 1, "{:?}", problems);
+    let problems =
+        config_problems(DataDogBuilder::default().align_timestamps(Duration::from_millis(1500)));
+    assert_eq!(problems.len(), 1, "{:?}", problems);
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn schedule_interval_test() -> anyhow::Result<()> {
+    for interval in [
+        Duration::ZERO,
+        Duration::from_millis(100),
+        Duration::from_millis(1500),
+    ] {
+        let exporter = DataDogBuilder::default().build()?;
+        assert!(matches!(exporter.schedule(interval), Err(Error::Config(_))));
+    }
+    let (_exporter, scheduled) = DataDogBuilder::default()
+        .build()?
+        .schedule(Duration::from_secs(1))?;
+    scheduled.abort();
+    Ok(())
+}
@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use httpmock::Method::GET;
+use httpmock::MockServer;
+use metrics::counter;
+use metrics_datadog_exporter::testing::TestRecorder;
+use metrics_datadog_exporter::{DataDogBuilder, Detector};
+use serde_json::json;
+
+fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+    let vars = vars
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect::<HashMap<_, _>>();
+    move |name| vars.get(name).cloned()
+}
+
+fn tags(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+    pairs
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+#[test]
+fn kubernetes_test() {
+    let vars = env(&[
+        ("KUBERNETES_SERVICE_HOST", "10.0.0.1"),
+        ("POD_NAMESPACE", "payments"),
+        ("POD_NAME", "api-7d9f-x2"),
+        ("KUBE_DEPLOYMENT", "api"),
+    ]);
+    assert_eq!(
+        Detector::Kubernetes.detect_with(vars),
+        tags(&[
+            ("kube_namespace", "payments"),
+            ("pod_name", "api-7d9f-x2"),
+            ("kube_deployment", "api"),
+        ])
+    );
+    assert!(Detector::Kubernetes
+        .detect_with(env(&[("POD_NAME", "api")]))
+        .is_empty());
+}
+
+#[test]
+fn lambda_test() {
+    let vars = env(&[
+        ("AWS_LAMBDA_FUNCTION_NAME", "checkout"),
+        ("AWS_REGION", "eu-west-1"),
+        ("AWS_LAMBDA_FUNCTION_MEMORY_SIZE", "512"),
+    ]);
+    assert_eq!(
+        Detector::Lambda.detect_with(vars),
+        tags(&[
+            ("functionname", "checkout"),
+            ("region", "eu-west-1"),
+            ("memorysize", "512"),
+        ])
+    );
+}
+
+#[test]
+fn ecs_test() {
+    let server = MockServer::start();
+    let mock = server.mock(|when, then| {
+        when.method(GET).path("/v4/task");
+        then.status(200).json_body(json!({
+            "Cluster": "arn:aws:ecs:eu-west-1:123456789012:cluster/prod",
+            "TaskARN": "arn:aws:ecs:eu-west-1:123456789012:task/prod/abc",
+            "Family": "api",
+            "Revision": "42",
+            "AvailabilityZone": "eu-west-1a"
+        }));
+    });
+
+    let vars = env(&[("ECS_CONTAINER_METADATA_URI_V4", &server.url("/v4"))]);
+    assert_eq!(
+        Detector::Ecs.detect_with(vars),
+        tags(&[
+            ("ecs_cluster_name", "prod"),
+            (
+                "task_arn",
+                "arn:aws:ecs:eu-west-1:123456789012:task/prod/abc"
+            ),
+            ("task_family", "api"),
+            ("task_version", "42"),
+            ("availability_zone", "eu-west-1a"),
+        ])
+    );
+    mock.assert();
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn ecs_build_in_runtime_test() -> Result<()> {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(GET).path("/v4/task");
+        then.status(200)
+            .delay(Duration::from_millis(500))
+            .json_body(json!({ "Family": "api" }));
+    });
+    std::env::set_var("ECS_CONTAINER_METADATA_URI_V4", server.url("/v4"));
+
+    let start = Instant::now();
+    let mut builder = DataDogBuilder::default().detect(Detector::Ecs);
+    #[cfg(feature = "stdout")]
+    {
+        builder = builder.write_to_stdout(false);
+    }
+    let test = TestRecorder::from_builder(builder)?;
+    assert!(start.elapsed() < Duration::from_millis(500));
+
+    let _guard = test.install();
+    counter!("requests").increment(1);
+    test.flush().await?;
+    assert!(!test.capture().has_tag("requests", "task_family", "api"));
+
+    std::thread::sleep(Duration::from_secs(1));
+    counter!("requests").increment(1);
+    test.flush().await?;
+    test.capture().assert_tag("requests", "task_family", "api");
+    Ok(())
+}
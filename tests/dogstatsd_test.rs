@@ -0,0 +1,103 @@
+use std::net::UdpSocket;
+use std::time::Duration;
+
+use anyhow::Result;
+use metrics::{counter, with_local_recorder};
+use metrics_datadog_exporter::{DataDogBuilder, DataDogEvent, DataDogServiceCheckStatus};
+
+fn listener() -> Result<(UdpSocket, String)> {
+    let socket = UdpSocket::bind("127.0.0.1:0")?;
+    socket.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let addr = socket.local_addr()?.to_string();
+    Ok((socket, addr))
+}
+
+fn receive(socket: &UdpSocket) -> Result<Vec<String>> {
+    let mut buf = [0; 8192];
+    let len = socket.recv(&mut buf)?;
+    Ok(String::from_utf8(buf[..len].to_vec())?
+        .lines()
+        .map(str::to_string)
+        .collect())
+}
+
+#[test]
+fn write_to_dogstatsd_test() -> Result<()> {
+    let (socket, addr) = listener()?;
+    let metrics = DataDogBuilder::default()
+        .write_to_stdout(false)
+        .write_to_dogstatsd(addr)
+        .tags(vec![("env".to_string(), "test".to_string())])
+        .build()?;
+
+    with_local_recorder(&metrics.recorder, || {
+        counter!("requests", "route" => "/").increment(2)
+    });
+    metrics.service_check("app.up", DataDogServiceCheckStatus::Ok, vec![], None);
+    metrics.event(DataDogEvent::new("Deploy".to_string(), "v2".to_string()));
+    let report = metrics.end_invocation("req-1")?;
+    assert!(report.dogstatsd.is_some_and(|o| o.is_success()));
+
+    let lines = receive(&socket)?;
+    assert_eq!(lines[0], "requests:2|c|#env:test,route:/");
+    assert!(lines[1].starts_with("_sc|app.up|0|d:"), "{:?}", lines);
+    assert!(lines[2].starts_with("_e{6,2}:Deploy|v2|d:"), "{:?}", lines);
+    Ok(())
+}
+
+#[test]
+fn lambda_extension_invocation_tags_test() -> Result<()> {
+    let (socket, addr) = listener()?;
+    let metrics = DataDogBuilder::default()
+        .lambda_extension()
+        .write_to_dogstatsd(addr)
+        .build()?;
+
+    with_local_recorder(&metrics.recorder, || counter!("invocations").increment(1));
+    metrics.end_invocation("req-1")?;
+    assert!(receive(&socket)?
+        .contains(&"invocations:1|c|#cold_start:true,request_id:req-1".to_string()));
+
+    with_local_recorder(&metrics.recorder, || counter!("invocations").increment(1));
+    metrics.end_invocation("req-2")?;
+    assert!(receive(&socket)?
+        .contains(&"invocations:1|c|#cold_start:false,request_id:req-2".to_string()));
+    Ok(())
+}
+
+#[test]
+fn dogstatsd_address_test() -> Result<()> {
+    for addr in ["localhost", "localhost:port", ":8125", "127.0.0.1:65536"] {
+        let result = DataDogBuilder::default()
+            .write_to_stdout(false)
+            .write_to_dogstatsd(addr.to_string())
+            .build();
+        assert!(
+            matches!(result, Err(metrics_datadog_exporter::Error::Config(_))),
+            "{}",
+            addr
+        );
+    }
+    Ok(())
+}
+
+#[cfg(all(feature = "api", not(feature = "blocking")))]
+#[test]
+fn lambda_extension_with_api_requires_blocking_test() -> Result<()> {
+    let (_socket, addr) = listener()?;
+    let result = DataDogBuilder::default()
+        .lambda_extension()
+        .write_to_api(true, Some("DUMMY".to_string()))
+        .build();
+    assert!(matches!(
+        result,
+        Err(metrics_datadog_exporter::Error::Config(_))
+    ));
+
+    DataDogBuilder::default()
+        .write_to_stdout(false)
+        .write_to_dogstatsd(addr)
+        .write_to_api(true, Some("DUMMY".to_string()))
+        .build()?;
+    Ok(())
+}
@@ -0,0 +1,233 @@
+use anyhow::Result;
+use metrics::{counter, gauge, histogram, with_local_recorder};
+use metrics_datadog_exporter::{
+    DataDogBuilder, DataDogEvent, DataDogServiceCheckStatus, ManualClock, OutputFormat,
+    TagMergePolicy,
+};
+use serde_json::Value;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+#[derive(Clone, Default)]
+struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+impl Buffer {
+    fn take(&self) -> String {
+        String::from_utf8(std::mem::take(&mut *self.0.lock().unwrap())).unwrap()
+    }
+}
+
+impl Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+async fn flush_with(format: OutputFormat) -> Result<String> {
+    let buffer = Buffer::default();
+    let (recorder, exporter) = DataDogBuilder::default()
+        .stdout_writer(buffer.clone())
+        .stdout_format(format)
+        .build_recorder()?;
+    with_local_recorder(&recorder, || {
+        counter!("requests", "route" => "/").increment(3);
+        gauge!("queue").set(1.5);
+    });
+    exporter.service_check("app.up", DataDogServiceCheckStatus::Ok, vec![], None);
+    exporter.flush().await?;
+    Ok(buffer.take())
+}
+
+#[tokio::test]
+async fn lambda_format_test() -> Result<()> {
+    let output = flush_with(OutputFormat::Lambda).await?;
+    let lines = output
+        .lines()
+        .map(serde_json::from_str)
+        .collect::<Result<Vec<Value>, _>>()?;
+    let requests = lines.iter().find(|l| l["m"] == "requests").unwrap();
+    assert_eq!(requests["v"], 3);
+    assert_eq!(requests["t"][0], "route:/");
+    assert!(lines.iter().any(|l| l["check"] == "app.up"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn series_format_test() -> Result<()> {
+    let output = flush_with(OutputFormat::Series).await?;
+    let post: Value = serde_json::from_str(output.lines().next().unwrap())?;
+    let series = post["series"].as_array().unwrap();
+    let queue = series.iter().find(|s| s["metric"] == "queue").unwrap();
+    assert_eq!(queue["type"], "gauge");
+    assert_eq!(queue["points"][0][1], 1.5);
+    Ok(())
+}
+
+#[tokio::test]
+async fn series_flush_interval_test() -> Result<()> {
+    let buffer = Buffer::default();
+    let (recorder, exporter) = DataDogBuilder::default()
+        .stdout_writer(buffer.clone())
+        .stdout_format(OutputFormat::Series)
+        .align_timestamps(Duration::from_secs(10))
+        .build_recorder()?;
+    with_local_recorder(&recorder, || counter!("requests").increment(3));
+    exporter.flush().await?;
+    let post: Value = serde_json::from_str(buffer.take().lines().next().unwrap())?;
+    assert_eq!(post["series"][0]["interval"], 10);
+    Ok(())
+}
+
+#[tokio::test]
+async fn dogstatsd_format_test() -> Result<()> {
+    let output = flush_with(OutputFormat::DogStatsD).await?;
+    let lines = output.lines().collect::<Vec<_>>();
+    assert!(lines.contains(&"requests:3|c|#route:/"));
+    assert!(lines.contains(&"queue:1.5|g"));
+    assert!(lines.iter().any(|l| l.starts_with("_sc|app.up|0")));
+    Ok(())
+}
+
+#[tokio::test]
+async fn table_format_test() -> Result<()> {
+    let output = flush_with(OutputFormat::Table).await?;
+    let mut lines = output.lines();
+    assert_eq!(
+        lines.next().unwrap().split_whitespace().collect::<Vec<_>>(),
+        vec!["METRIC", "TYPE", "VALUE", "TAGS"]
+    );
+    assert!(
+        output
+            .lines()
+            .any(|l| l.split_whitespace().collect::<Vec<_>>()
+                == ["requests", "count", "3", "route:/"])
+    );
+    assert!(output.contains("CHECK"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn console_summary_test() -> Result<()> {
+    let buffer = Buffer::default();
+    let (recorder, exporter) = DataDogBuilder::default()
+        .stdout_writer(buffer.clone())
+        .console_summary()
+        .build_recorder()?;
+    let row = |output: &str, name: &str| {
+        output
+            .lines()
+            .find(|l| l.starts_with(name))
+            .map(|l| l.split_whitespace().map(str::to_string).collect::<Vec<_>>())
+            .unwrap()
+    };
+
+    with_local_recorder(&recorder, || {
+        counter!("requests", "route" => "/").increment(3);
+        gauge!("queue").set(4.0);
+        for value in 1..=100 {
+            histogram!("latency").record(value as f64);
+        }
+    });
+    exporter.flush().await?;
+    let output = buffer.take();
+    assert_eq!(row(&output, "requests")[..3], ["requests", "route:/", "3"]);
+    assert_eq!(row(&output, "latency")[1..], ["100", "50", "95", "99"]);
+
+    with_local_recorder(&recorder, || {
+        counter!("requests", "route" => "/").increment(2);
+        gauge!("queue").set(1.0);
+    });
+    exporter.flush().await?;
+    let output = buffer.take();
+    assert_eq!(row(&output, "requests")[..3], ["requests", "route:/", "5"]);
+    assert_eq!(row(&output, "queue")[1..], ["1", "1", "4"]);
+    assert_eq!(row(&output, "latency")[1..], ["0", "-", "-", "-"]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn self_telemetry_opt_out_test() -> Result<()> {
+    for builder in [
+        DataDogBuilder::default().self_telemetry(false),
+        DataDogBuilder::default().filter("metrics_datadog_exporter=off".to_string()),
+    ] {
+        let buffer = Buffer::default();
+        let (_, exporter) = builder.stdout_writer(buffer.clone()).build_recorder()?;
+        exporter.flush().await?;
+        exporter.flush().await?;
+        assert_eq!(buffer.take(), "");
+        assert_eq!(exporter.stats().flushes, 2);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn check_and_event_tags_test() -> Result<()> {
+    for (policy, env) in [
+        (TagMergePolicy::MetricWins, vec!["env:staging"]),
+        (TagMergePolicy::GlobalWins, vec!["env:prod"]),
+        (TagMergePolicy::KeepBoth, vec!["env:prod", "env:staging"]),
+    ] {
+        let buffer = Buffer::default();
+        let (_, exporter) = DataDogBuilder::default()
+            .tags(vec![("env".to_string(), "prod".to_string())])
+            .tag_provider(|| vec![("color".to_string(), "blue".to_string())])
+            .tag_merge_policy(policy)
+            .self_telemetry(false)
+            .stdout_writer(buffer.clone())
+            .build_recorder()?;
+        exporter.service_check(
+            "app.up",
+            DataDogServiceCheckStatus::Ok,
+            vec![
+                ("env".to_string(), "staging".to_string()),
+                ("env".to_string(), "staging".to_string()),
+            ],
+            None,
+        );
+        exporter.event(
+            DataDogEvent::new("Deploy".to_string(), "v1".to_string())
+                .tags(vec![("env".to_string(), "staging".to_string())]),
+        );
+        exporter.flush().await?;
+
+        let mut expected = vec!["color:blue"];
+        expected.extend(env);
+        let lines = buffer
+            .take()
+            .lines()
+            .map(serde_json::from_str)
+            .collect::<Result<Vec<Value>, _>>()?;
+        assert_eq!(lines.len(), 2);
+        for line in lines {
+            assert_eq!(line["tags"], serde_json::json!(expected), "{:?}", policy);
+        }
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn event_clock_test() -> Result<()> {
+    let buffer = Buffer::default();
+    let (_, exporter) = DataDogBuilder::default()
+        .clock(ManualClock::from_timestamp(1_700_000_000))
+        .self_telemetry(false)
+        .stdout_writer(buffer.clone())
+        .stdout_format(OutputFormat::DogStatsD)
+        .build_recorder()?;
+    exporter.event(DataDogEvent::new("Deploy".to_string(), "v1".to_string()));
+    exporter.event(
+        DataDogEvent::new("Rollback".to_string(), "v0".to_string()).date_happened(1_600_000_000),
+    );
+    exporter.flush().await?;
+    assert_eq!(
+        buffer.take(),
+        "_e{6,2}:Deploy|v1|d:1700000000\n_e{8,2}:Rollback|v0|d:1600000000\n"
+    );
+    Ok(())
+}
@@ -0,0 +1,67 @@
+use anyhow::Result;
+use metrics::{counter, gauge, histogram};
+use metrics_datadog_exporter::testing::TestRecorder;
+
+async fn record_requests(count: u64) -> Result<()> {
+    let test = TestRecorder::new();
+    let _guard = test.install();
+    for _ in 0..count {
+        counter!("requests", "route" => "/", "method" => "GET").increment(1);
+        tokio::task::yield_now().await;
+    }
+    test.flush().await?;
+    test.capture()
+        .assert_counter("requests", &[("route", "/")], count);
+    Ok(())
+}
+
+#[tokio::test]
+async fn parallel_test_a() -> Result<()> {
+    record_requests(3).await
+}
+
+#[tokio::test]
+async fn parallel_test_b() -> Result<()> {
+    record_requests(5).await
+}
+
+#[tokio::test]
+async fn capture_helpers_test() -> Result<()> {
+    let test = TestRecorder::new();
+    {
+        let _guard = test.install();
+        counter!("jobs", "queue" => "high").increment(2);
+        gauge!("workers").set(4.0);
+        histogram!("latency", "queue" => "high").record(1.0);
+        histogram!("latency", "queue" => "high").record(2.0);
+    }
+    counter!("jobs", "queue" => "high").increment(100);
+    test.flush().await?;
+
+    let capture = test.capture();
+    assert_eq!(capture.counter_value("jobs", &[("queue", "high")]), Some(2));
+    assert_eq!(capture.counter_value("jobs", &[("queue", "low")]), None);
+    assert_eq!(capture.gauge_value("workers", &[]), Some(4.0));
+    capture.assert_histogram_count("latency", &[("queue", "high")], 2);
+    capture.assert_tag("latency", "queue", "high");
+    assert!(!capture.has_tag("workers", "queue", "high"));
+
+    {
+        let _guard = test.install();
+        counter!("jobs", "queue" => "high").increment(1);
+    }
+    test.flush().await?;
+    capture.assert_counter("jobs", &[], 3);
+
+    capture.clear();
+    assert!(capture.metrics().is_empty());
+    Ok(())
+}
+
+#[test]
+#[should_panic(expected = "counter `missing`")]
+fn assert_counter_panics_test() {
+    TestRecorder::new()
+        .capture()
+        .assert_counter("missing", &[], 1);
+}